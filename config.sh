@@ -1,12 +1,16 @@
 #!/bin/bash
 
-NUM_THINKERS=7
-NUM_FORKS=7
-NUM_THINKERS_FEDORA=5
-NUM_FORKS_FEDORA=4
-NUM_THINKERS_MAC=2
-NUM_FORKS_MAC=3
+HOSTS_FILE=./lab.hosts
+FEDORA_HOST=192.168.51.10
+MAC_HOST=192.168.51.11
 NUM_NEXT_THINKERS=4
 NUM_TOKENS=3
+# Every host generates its own configs, the same seed gives them the same ids
+SEED=51
 VISUALIZER_ADDRESS=192.168.51.10:3334
-INIT_SERVER_ADDRESS=192.168.51.10:3333
+
+# Writes the configs of the nodes on the given host to ./config/
+generate_configs() {
+  rm -rf ./config/
+  ./target/release/init generate --hosts $HOSTS_FILE --next-thinkers-amount $NUM_NEXT_THINKERS --tokens $NUM_TOKENS --seed $SEED --host $1 --out-dir ./config/
+}
@@ -1,12 +1,7 @@
 #!/bin/bash
 
-source ./config.sh
-NUM_FORKS=$1
-START_FORK_COMMAND="./target/release/fork init-server 0.0.0.0:0 --init-server $INIT_SERVER_ADDRESS --save-config-dir ./config/ || sleep 100"
-
 ptyxis --new-window -- bash -c "
-NUM_FORKS=$NUM_FORKS
-for ((i = 0; i < NUM_FORKS; i++)); do
-  ptyxis --tab --title \"Fork $i\" -- bash -c \"$START_FORK_COMMAND\"
+for config in ./config/fork_*.conf; do
+  ptyxis --tab --title \"Fork \$config\" -- bash -c \"./target/release/fork config \$config || sleep 100\"
 done
 "
@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use philosopher_nom_nom_ring::init_logger;
+use philosopher_nom_nom_ring::lib::visualizer::analysis::Summary;
+use philosopher_nom_nom_ring::lib::visualizer::history::RunHistory;
+use philosopher_nom_nom_ring::lib::visualizer::trace::read_trace;
+
+/// Reports throughput, fairness, token losses and safety of a run recorded with `visualizer --record`
+#[derive(Parser, Debug)]
+pub struct AnalyzeCli {
+    trace: PathBuf,
+    /// Also writes the summary as JSON, `-` for stdout instead of the text report
+    #[arg(short, long)]
+    json: Option<PathBuf>,
+}
+
+fn main() {
+    init_logger();
+    let cli = AnalyzeCli::parse();
+
+    let records = read_trace(&cli.trace).unwrap_or_else(|e| panic!("{e}"));
+    let history = RunHistory::from_trace(&records).unwrap_or_else(|e| panic!("{e}"));
+    let summary = Summary::analyze(&history);
+    let json = serde_json::to_string_pretty(&summary).unwrap();
+    match cli.json {
+        Some(path) if path.as_os_str() == "-" => println!("{json}"),
+        Some(path) => {
+            std::fs::write(&path, json + "\n")
+                .unwrap_or_else(|e| panic!("Could not write {}: {e}", path.display()));
+            print!("{summary}");
+        }
+        None => print!("{summary}"),
+    }
+}
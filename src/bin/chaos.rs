@@ -0,0 +1,65 @@
+use std::fs::File;
+use std::io::{Write, stdout};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::Parser;
+use clap::builder::RangedU64ValueParser;
+use philosopher_nom_nom_ring::init_logger;
+use philosopher_nom_nom_ring::lib::chaos::{Scenario, UdpChaosTarget, run_scenario};
+use philosopher_nom_nom_ring::lib::cluster::ClusterBuilder;
+use philosopher_nom_nom_ring::lib::utils::parse_duration;
+
+#[derive(Parser, Debug)]
+pub struct ChaosCli {
+    scenario: PathBuf,
+    /// Directory of saved configs of the running cluster
+    #[arg(short, long, default_value = "./config")]
+    config_dir: PathBuf,
+    /// `--event-log` files of the running nodes, what they log during the scenario goes to the trace
+    #[arg(short, long, num_args = 1..)]
+    event_logs: Vec<PathBuf>,
+    /// Runs the scenario against a cluster of this many thinkers in this process instead
+    #[arg(long, conflicts_with_all = ["config_dir", "event_logs"])]
+    in_process: Option<usize>,
+    #[arg(long, default_value_t = 1, requires = "in_process")]
+    tokens: usize,
+    #[arg(long, default_value_t = 1, requires = "in_process", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    fork_replicas: usize,
+    #[arg(long, requires = "in_process")]
+    seed: Option<u64>,
+    /// How long the cluster keeps running after the last step
+    #[arg(short, long, value_parser = parse_duration, default_value = "10s")]
+    settle: Duration,
+    /// File the events of the cluster and the executed steps are written to, defaults to stdout
+    #[arg(short, long)]
+    trace: Option<PathBuf>,
+}
+
+fn main() {
+    init_logger();
+    let cli = ChaosCli::parse();
+    let scenario = Scenario::read(&cli.scenario).unwrap_or_else(|e| panic!("{e}"));
+    let mut trace: Box<dyn Write> = match &cli.trace {
+        Some(path) => Box::new(File::create(path).unwrap()),
+        None => Box::new(stdout()),
+    };
+    log::info!("Running scenario with {} steps", scenario.steps.len());
+    match cli.in_process {
+        Some(thinkers) => {
+            let mut builder = ClusterBuilder::new(thinkers)
+                .tokens(cli.tokens)
+                .fork_replicas(cli.fork_replicas);
+            if let Some(seed) = cli.seed {
+                builder = builder.seed(seed);
+            }
+            run_scenario(&scenario, &mut builder.build(), cli.settle, &mut trace);
+        }
+        None => {
+            let mut target =
+                UdpChaosTarget::from_config_dir(&cli.config_dir).with_event_logs(cli.event_logs);
+            run_scenario(&scenario, &mut target, cli.settle, &mut trace);
+        }
+    }
+    log::info!("Scenario finished");
+}
@@ -0,0 +1,137 @@
+use std::net::{SocketAddr, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::{ArgAction, Parser, Subcommand};
+use philosopher_nom_nom_ring::init_logger;
+use philosopher_nom_nom_ring::lib::config::read_config_dir;
+use philosopher_nom_nom_ring::lib::faults::parse_percentage;
+use philosopher_nom_nom_ring::lib::messages::{ControlMessage, ForkMessages, ThinkerMessage};
+use philosopher_nom_nom_ring::lib::transceiver::Transceiver;
+
+#[derive(Subcommand, Debug)]
+enum Action {
+    /// Crash the node and restart it after the given amount of seconds
+    Crash {
+        #[arg(value_parser = parse_seconds)]
+        seconds: Duration,
+    },
+    CrashPermanently,
+    Pause,
+    Resume,
+    /// Drop the given fraction (0.0 - 1.0) of outgoing messages
+    Drop {
+        #[arg(value_parser = parse_percentage)]
+        percentage: f64,
+    },
+    RandomCrashes {
+        #[arg(action = ArgAction::Set)]
+        enabled: bool,
+    },
+}
+
+fn parse_seconds(value: &str) -> Result<Duration, String> {
+    let seconds = value
+        .parse::<f64>()
+        .map_err(|_| format!("Invalid number of seconds {value}"))?;
+    Duration::try_from_secs_f64(seconds)
+        .map_err(|e| format!("Invalid number of seconds {value}: {e}"))
+}
+
+impl From<Action> for ControlMessage {
+    fn from(value: Action) -> Self {
+        match value {
+            Action::Crash { seconds } => ControlMessage::Crash { duration: seconds },
+            Action::CrashPermanently => ControlMessage::CrashPermanently,
+            Action::Pause => ControlMessage::Pause,
+            Action::Resume => ControlMessage::Resume,
+            Action::Drop { percentage } => ControlMessage::DropMessages { percentage },
+            Action::RandomCrashes { enabled } => ControlMessage::RandomCrashes(enabled),
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Role {
+    Thinker {
+        /// Address of the thinker or a prefix of its id
+        target: String,
+        #[command(subcommand)]
+        action: Action,
+    },
+    Fork {
+        /// Address of a fork replica or a prefix of the fork id, which targets all its replicas
+        target: String,
+        #[command(subcommand)]
+        action: Action,
+    },
+}
+
+#[derive(Parser, Debug)]
+pub struct CtlCli {
+    #[command(subcommand)]
+    role: Role,
+    /// Directory of saved configs, used to resolve ids to addresses
+    #[arg(short, long, default_value = "./config")]
+    config_dir: PathBuf,
+}
+
+fn resolve_addresses(
+    target: &str,
+    config_dir: &Path,
+    candidates: impl Fn(&Path) -> Vec<(String, SocketAddr)>,
+) -> Vec<SocketAddr> {
+    if let Ok(address) = target.parse::<SocketAddr>() {
+        return vec![address];
+    }
+    let matches = candidates(config_dir)
+        .into_iter()
+        .filter(|(id, _)| id.starts_with(target))
+        .collect::<Vec<_>>();
+    let first_id = matches.first().map(|(id, _)| id.clone());
+    if matches.is_empty() {
+        panic!("No node with id {target} in {}", config_dir.display());
+    }
+    if matches.iter().any(|(id, _)| Some(id) != first_id.as_ref()) {
+        panic!("Id prefix {target} is ambiguous: {:?}", matches);
+    }
+    matches.into_iter().map(|(_, address)| address).collect()
+}
+
+fn main() {
+    init_logger();
+    let cli = CtlCli::parse();
+    let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+    let transceiver = Transceiver::new(socket);
+
+    match cli.role {
+        Role::Thinker { target, action } => {
+            let addresses = resolve_addresses(&target, &cli.config_dir, |dir| {
+                read_config_dir(dir)
+                    .0
+                    .into_iter()
+                    .map(|config| (config.id.value.to_string(), config.address))
+                    .collect()
+            });
+            let message = ControlMessage::from(action);
+            for address in addresses {
+                log::info!("Sending {:?} to thinker {address}", message);
+                transceiver.send_reliable(ThinkerMessage::Control(message.clone()), &address);
+            }
+        }
+        Role::Fork { target, action } => {
+            let addresses = resolve_addresses(&target, &cli.config_dir, |dir| {
+                read_config_dir(dir)
+                    .1
+                    .into_iter()
+                    .map(|config| (config.id.value.to_string(), config.address))
+                    .collect()
+            });
+            let message = ControlMessage::from(action);
+            for address in addresses {
+                log::info!("Sending {:?} to fork {address}", message);
+                transceiver.send_reliable(ForkMessages::Control(message.clone()), &address);
+            }
+        }
+    }
+}
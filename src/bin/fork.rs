@@ -1,128 +1,9 @@
-use std::net::{SocketAddr, UdpSocket};
-use std::path::PathBuf;
-use std::thread::sleep;
-
-use clap::{Parser, Subcommand};
-use philosopher_nom_nom_ring::lib::config::Config;
-use philosopher_nom_nom_ring::lib::fork::{Fork, ForkInitParams};
-use philosopher_nom_nom_ring::lib::messages::{ForkMessages, InitMessages};
-use philosopher_nom_nom_ring::lib::transceiver::Transceiver;
-use philosopher_nom_nom_ring::lib::utils::Id;
-use philosopher_nom_nom_ring::lib::visualizer::VisualizerRef;
-use philosopher_nom_nom_ring::{
-    MAX_CRASH_DURATION, MIN_CRASH_DURATION, NETWORK_BUFFER_SIZE, TICK_INTERVAL, init_logger,
-    should_crash,
-};
-use rand::Rng;
-use rkyv::{Archive, Deserialize, Serialize};
-
-#[derive(Subcommand, Debug)]
-enum Commands {
-    Config {
-        config_file: PathBuf,
-    },
-    InitServer {
-        address: SocketAddr,
-        #[arg(short, long)]
-        save_config_dir: Option<PathBuf>,
-        #[arg(short, long)]
-        init_server: SocketAddr,
-    },
-}
-
-#[derive(Debug, Serialize, Deserialize, Archive)]
-pub struct ForkConfig {
-    id: Id<Fork>,
-    address: SocketAddr,
-    visualizer: Option<VisualizerRef>,
-}
-
-#[derive(Parser, Debug)]
-pub struct ForkCli {
-    #[command(subcommand)]
-    command: Commands,
-}
+use clap::Parser;
+use philosopher_nom_nom_ring::init_logger;
+use philosopher_nom_nom_ring::lib::fork::Fork;
+use philosopher_nom_nom_ring::lib::runner::{NodeCli, run_node};
 
 fn main() {
     init_logger();
-    let cli = ForkCli::parse();
-
-    let mut buffer = [0; NETWORK_BUFFER_SIZE];
-    let mut unhandled_messages = vec![];
-    let init_params = match cli.command {
-        Commands::Config { config_file } => {
-            let config = ForkConfig::read(&config_file);
-            let socket = UdpSocket::bind(config.address).unwrap();
-            let transceiver = Transceiver::new(socket);
-            ForkInitParams {
-                id: config.id,
-                visualizer: config.visualizer,
-                transceiver,
-                unhandled_messages,
-            }
-        }
-        Commands::InitServer {
-            address,
-            save_config_dir,
-            init_server,
-        } => {
-            let socket = UdpSocket::bind(address).unwrap();
-            let transceiver = Transceiver::new(socket);
-            let id = Id::random();
-
-            transceiver.send_reliable(InitMessages::ForkRequest(id.clone()), &init_server);
-            let visualizer = 'outer: loop {
-                while let Some(message) = transceiver.receive::<ForkMessages>(&mut buffer) {
-                    match message {
-                        (ForkMessages::Init(init_thinker_params), _) => {
-                            break 'outer init_thinker_params;
-                        }
-                        message => {
-                            unhandled_messages.push(message);
-                        }
-                    }
-                }
-                sleep(TICK_INTERVAL);
-            };
-            if let Some(path) = save_config_dir {
-                ForkConfig {
-                    id: id.clone(),
-                    visualizer: visualizer.clone(),
-                    address: transceiver.local_address(),
-                }
-                .write(&path.join(format!("fork_{}.conf", id.value)));
-            }
-            ForkInitParams {
-                id,
-                visualizer,
-                transceiver,
-                unhandled_messages,
-            }
-        }
-    };
-
-    let mut fork = Fork::new(init_params);
-    loop {
-        fork.print_started();
-        loop {
-            fork.tick(&mut buffer);
-            fork.update_visualizer();
-            sleep(TICK_INTERVAL);
-            match should_crash() {
-                philosopher_nom_nom_ring::CrashStatus::Continue => (),
-                philosopher_nom_nom_ring::CrashStatus::Crash => {
-                    let crash_duration =
-                        rand::rng().random_range(MIN_CRASH_DURATION..=MAX_CRASH_DURATION);
-                    log::info!("Fork crashed. Restarting in {:?}", (crash_duration));
-
-                    sleep(crash_duration);
-                    fork = fork.reset();
-                    break;
-                }
-                philosopher_nom_nom_ring::CrashStatus::PermanentCrash => {
-                    panic!("Permanently crashed")
-                }
-            }
-        }
-    }
+    run_node::<Fork>(NodeCli::parse());
 }
@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use philosopher_nom_nom_ring::init_logger;
+use philosopher_nom_nom_ring::lib::visualizer::gantt;
+use philosopher_nom_nom_ring::lib::visualizer::history::RunHistory;
+use philosopher_nom_nom_ring::lib::visualizer::trace::read_trace;
+
+/// Draws a run recorded with `visualizer --record` as SVG timeline
+#[derive(Parser, Debug)]
+pub struct GanttCli {
+    trace: PathBuf,
+    /// Written to stdout if missing
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Width of the image in pixels
+    #[arg(long, default_value_t = 1200)]
+    width: usize,
+}
+
+fn main() {
+    init_logger();
+    let cli = GanttCli::parse();
+
+    let records = read_trace(&cli.trace).unwrap_or_else(|e| panic!("{e}"));
+    let history = RunHistory::from_trace(&records).unwrap_or_else(|e| panic!("{e}"));
+    let svg = gantt::render(&history, cli.width);
+    match cli.output {
+        Some(path) => std::fs::write(&path, svg)
+            .unwrap_or_else(|e| panic!("Could not write {}: {e}", path.display())),
+        None => print!("{svg}"),
+    }
+}
@@ -1,20 +1,71 @@
-use std::net::{SocketAddr, UdpSocket};
+use std::net::{IpAddr, SocketAddr, UdpSocket};
 
-use clap::Parser;
+use std::fs;
+use std::path::PathBuf;
+
+use clap::builder::RangedU64ValueParser;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use philosopher_nom_nom_ring::lib::deployment::{Deployment, parse_addresses, write_configs};
 use philosopher_nom_nom_ring::lib::fork::ForkRef;
 use philosopher_nom_nom_ring::lib::messages::ThinkerMessage;
-use philosopher_nom_nom_ring::lib::messages::VisualizerMessages;
-use philosopher_nom_nom_ring::lib::messages::thinker_messages::InitThinkerParams;
-use philosopher_nom_nom_ring::lib::messages::thinker_messages::Token;
 use philosopher_nom_nom_ring::lib::messages::{ForkMessages, InitMessages};
+use philosopher_nom_nom_ring::lib::runner::serve_metrics;
 use philosopher_nom_nom_ring::lib::thinker::ThinkerRef;
+use philosopher_nom_nom_ring::lib::topology::{MANIFEST_FILE, Topology};
 use philosopher_nom_nom_ring::lib::transceiver::Transceiver;
 use philosopher_nom_nom_ring::lib::visualizer::VisualizerRef;
 use philosopher_nom_nom_ring::{NETWORK_BUFFER_SIZE, init_logger};
-use rand::{rng, seq::SliceRandom};
+use rand::rngs::StdRng;
+use rand::{SeedableRng, rng, seq::SliceRandom};
 
 #[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct InitCli {
+    #[command(subcommand)]
+    command: Option<InitCommand>,
+    #[command(flatten)]
+    server: Option<ServerArgs>,
+}
+
+#[derive(Subcommand, Debug)]
+enum InitCommand {
+    /// Writes the configs of a whole deployment without starting an init server, the nodes are
+    /// then started with their `config` subcommand
+    Generate(GenerateArgs),
+}
+
+#[derive(Args, Debug)]
+struct GenerateArgs {
+    /// Thinker addresses in ring order, `host:port` or a port range like `10.0.0.1:4000-4004`
+    #[arg(long, num_args = 1.., value_parser = parse_addresses)]
+    thinkers: Vec<Vec<SocketAddr>>,
+    /// Fork addresses, `--fork-replicas` consecutive ones form one fork
+    #[arg(long, num_args = 1.., value_parser = parse_addresses)]
+    forks: Vec<Vec<SocketAddr>>,
+    #[arg(long, num_args = 1.., value_parser = parse_addresses)]
+    visualizers: Vec<Vec<SocketAddr>>,
+    /// File with one `<role> <addresses>` entry per line, added before the addresses above
+    #[arg(long)]
+    hosts: Option<PathBuf>,
+    #[arg(long)]
+    next_thinkers_amount: usize,
+    #[arg(long)]
+    tokens: usize,
+    #[arg(long, default_value_t = 1, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    fork_replicas: usize,
+    #[arg(short, long, default_value = "./config")]
+    out_dir: PathBuf,
+    /// Makes ids, meal timing and random crashes reproducible
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Only writes the configs of the nodes on this host, every host of a deployment then
+    /// generates its own with the same `--seed`
+    #[arg(long, requires = "seed")]
+    host: Option<IpAddr>,
+}
+
+#[derive(Args, Debug)]
+struct ServerArgs {
     address: SocketAddr,
     #[arg(long)]
     thinker: usize,
@@ -22,19 +73,79 @@ pub struct InitCli {
     next_thinkers_amount: usize,
     #[arg(long)]
     tokens: usize,
+    /// Visualizers to wait for, `--visualizer` alone waits for one
+    #[arg(long, default_value_t = 0, num_args = 0..=1, default_missing_value = "1")]
+    visualizer: usize,
+    /// Processes per fork, the first one starts as primary and the others as backups
+    #[arg(long, default_value_t = 1, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    fork_replicas: usize,
+    /// Writes the final ring as JSON to this file and as Graphviz DOT next to it
+    #[arg(long)]
+    topology: Option<PathBuf>,
+    /// Makes shuffling, token placement, ids, meal timing and random crashes reproducible,
+    /// nodes are placed by address before shuffling
     #[arg(long)]
-    visualizer: bool,
+    seed: Option<u64>,
+    /// Serves Prometheus metrics on `http://<address>/metrics`
+    #[arg(long)]
+    metrics: Option<SocketAddr>,
 }
 
 fn main() {
     init_logger();
-    let cli = InitCli::parse();
+    match InitCli::parse() {
+        InitCli {
+            command: Some(InitCommand::Generate(args)),
+            ..
+        } => generate(args),
+        InitCli {
+            server: Some(server),
+            ..
+        } => serve(server),
+        _ => InitCli::command().print_help().unwrap(),
+    }
+}
+
+fn generate(args: GenerateArgs) {
+    let mut deployment = args
+        .hosts
+        .as_ref()
+        .map(|path| Deployment::read(path).unwrap_or_else(|e| panic!("{e}")))
+        .unwrap_or_default();
+    deployment
+        .thinkers
+        .extend(args.thinkers.into_iter().flatten());
+    deployment.forks.extend(args.forks.into_iter().flatten());
+    deployment
+        .visualizers
+        .extend(args.visualizers.into_iter().flatten());
+    let mut topology = deployment
+        .topology(args.tokens, args.next_thinkers_amount, args.fork_replicas)
+        .unwrap_or_else(|e| panic!("{e}"));
+    if let Some(seed) = args.seed {
+        topology = topology.with_seed(seed);
+    }
+    fs::create_dir_all(&args.out_dir).unwrap();
+    let (thinkers, forks) = write_configs(&topology, &args.out_dir, args.host);
+    topology
+        .export(&args.out_dir.join(MANIFEST_FILE))
+        .unwrap_or_else(|e| panic!("{e}"));
+    log::info!(
+        "Wrote configs of {thinkers} thinkers and {forks} fork replicas to {}",
+        args.out_dir.display()
+    );
+}
+
+fn serve(cli: ServerArgs) {
     let socket = UdpSocket::bind(cli.address).unwrap();
     let mut waiting_forks: Vec<ForkRef> = vec![];
     let mut waiting_thinkers: Vec<ThinkerRef> = vec![];
-    let mut waiting_visualizer: Option<VisualizerRef> = None;
+    let mut waiting_visualizers: Vec<VisualizerRef> = vec![];
 
-    let transceiver: Transceiver = Transceiver::new(socket);
+    let mut transceiver: Transceiver = Transceiver::new(socket);
+    if let Some(address) = cli.metrics {
+        serve_metrics(transceiver.metrics(), address);
+    }
 
     let mut buffer = [0; NETWORK_BUFFER_SIZE];
     log::info!("Started init server, {:?}", cli);
@@ -43,10 +154,11 @@ fn main() {
             buffer = [0; NETWORK_BUFFER_SIZE];
             match message {
                 InitMessages::ForkRequest(id) => {
-                    if cli.thinker > waiting_forks.len() {
+                    if cli.thinker * cli.fork_replicas > waiting_forks.len() {
                         waiting_forks.push(ForkRef {
                             address: entity,
                             id,
+                            backups: vec![],
                         });
                         log::info!("Added fork {entity} to queue");
                     } else {
@@ -68,35 +180,39 @@ fn main() {
                         )
                     }
                 }
-                InitMessages::VisualizerRequest => {
-                    if cli.visualizer && waiting_visualizer.is_none() {
-                        let _ = waiting_visualizer.insert(VisualizerRef { address: entity });
-                        log::info!("Set visualizer {entity}");
-                    } else if waiting_visualizer.is_some() {
+                InitMessages::VisualizerRequest { kinds } => {
+                    if waiting_visualizers
+                        .iter()
+                        .any(|visualizer| visualizer.address == entity)
+                    {
+                        log::warn!("Visualizer {entity} is already waiting.");
+                    } else if cli.visualizer > waiting_visualizers.len() {
+                        waiting_visualizers.push(VisualizerRef {
+                            address: entity,
+                            kinds,
+                        });
+                        log::info!("Added visualizer {entity} to queue");
+                    } else if cli.visualizer == 0 {
                         log::warn!(
-                            "Additional visualizer {entity} tried to connect, but one is already waiting."
+                            "Expected no visualizer because --visualizer was not passed as an cli argument."
                         );
                     } else {
                         log::warn!(
-                            "Expected no visualizer because --visualizer was not passed as an cli argument."
+                            "Additional visualizer {entity} tried to connect, but queue was already full."
                         );
                     }
                 }
             }
             if cli.thinker == waiting_thinkers.len()
-                && cli.thinker == waiting_forks.len()
-                && (!cli.visualizer || waiting_visualizer.is_some())
+                && cli.thinker * cli.fork_replicas == waiting_forks.len()
+                && cli.visualizer == waiting_visualizers.len()
             {
-                let tokens = (0..cli.tokens)
-                    .map(|index| Token::create(waiting_thinkers[index].id.clone()))
-                    .collect();
                 notify_entities(
                     waiting_thinkers,
                     waiting_forks,
-                    tokens,
-                    waiting_visualizer,
+                    &cli,
+                    waiting_visualizers,
                     &transceiver,
-                    cli.next_thinkers_amount,
                 );
                 log::info!("Notified all queued entities. Shutting down");
                 return;
@@ -108,49 +224,48 @@ fn main() {
 fn notify_entities(
     mut thinkers: Vec<ThinkerRef>,
     mut forks: Vec<ForkRef>,
-    tokens: Vec<Token>,
-    visualizer: Option<VisualizerRef>,
+    cli: &ServerArgs,
+    visualizers: Vec<VisualizerRef>,
     transceiver: &Transceiver,
-    amount_next_thinkers: usize,
 ) {
-    thinkers.shuffle(&mut rng());
-    forks.shuffle(&mut rng());
-
-    for i in 0..thinkers.len() {
-        let forks_of_thinker = (0..2)
-            .map(|index| {
-                let next_index = (index + i) % forks.len();
-                forks[next_index].clone()
-            })
-            .collect::<Vec<_>>()
-            .try_into()
-            .unwrap();
-
-        let next_thinkers = (1..=amount_next_thinkers)
-            .map(|index| {
-                let next_index = (index + i) % thinkers.len();
-                thinkers[next_index].clone()
-            })
-            .collect();
-
-        let token = tokens.iter().find(|token| token.issuer.eq(&thinkers[i].id));
+    match cli.seed {
+        Some(seed) => {
+            // Arrival order is up to the network, only the addresses can be reproduced
+            thinkers.sort_by_key(|thinker| thinker.address);
+            forks.sort_by_key(|fork| fork.address);
+            let mut rng = StdRng::seed_from_u64(seed);
+            thinkers.shuffle(&mut rng);
+            forks.shuffle(&mut rng);
+        }
+        None => {
+            thinkers.shuffle(&mut rng());
+            forks.shuffle(&mut rng());
+        }
+    }
+    let mut topology = Topology::ring(
+        thinkers,
+        forks,
+        cli.tokens,
+        visualizers,
+        cli.next_thinkers_amount,
+        cli.fork_replicas,
+    );
+    if let Some(seed) = cli.seed {
+        topology = topology.with_seed(seed);
+    }
+    if let Some(path) = &cli.topology {
+        topology.export(path).unwrap_or_else(|e| panic!("{e}"));
+        log::info!("Wrote topology to {}", path.display());
+    }
 
-        let message = ThinkerMessage::Init(InitThinkerParams {
-            token: token.cloned(),
-            forks: forks_of_thinker,
-            next_thinkers,
-            visualizer: visualizer.clone(),
-            available_tokens: tokens.iter().map(|token| token.into()).collect(),
-        });
-        transceiver.send_reliable(message, &thinkers[i].address);
+    for (index, thinker) in topology.thinkers.iter().enumerate() {
+        let message = ThinkerMessage::Init(Box::new(topology.thinker_init(index)));
+        transceiver.send_reliable(message, &thinker.address);
+    }
+    for (address, init_params) in topology.fork_inits() {
+        transceiver.send_reliable(ForkMessages::Init(init_params), &address);
     }
-    forks.iter().for_each(|fork| {
-        transceiver.send_reliable(ForkMessages::Init(visualizer.clone()), &fork.address);
-    });
-    if let Some(visualizer) = visualizer {
-        transceiver.send_reliable(
-            VisualizerMessages::Init { thinkers, forks },
-            &visualizer.address,
-        );
+    for visualizer in &topology.visualizers {
+        transceiver.send_reliable(topology.visualizer_init(), &visualizer.address);
     }
 }
@@ -0,0 +1,178 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::sleep;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use clap::Parser;
+use philosopher_nom_nom_ring::lib::config::read_config_dir;
+use philosopher_nom_nom_ring::lib::events::{Event, read_event_log};
+use philosopher_nom_nom_ring::lib::fork::Fork;
+use philosopher_nom_nom_ring::lib::node::Node;
+use philosopher_nom_nom_ring::lib::thinker::Thinker;
+use philosopher_nom_nom_ring::lib::utils::parse_duration;
+use philosopher_nom_nom_ring::{TICK_INTERVAL, init_logger};
+use signal_hook::consts::{SIGINT, SIGTERM};
+use uuid::Uuid;
+
+/// How long the nodes get to shut down gracefully before they are killed
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+#[derive(Parser, Debug)]
+pub struct RestoreCli {
+    /// Directory the nodes saved their configs to with `--save-config-dir`
+    config_dir: PathBuf,
+    /// Logs and event logs of the restored nodes, the tokens are tracked through the event logs
+    #[arg(short, long, default_value = "./restore-logs")]
+    log_dir: PathBuf,
+    /// How long to wait for the tokens to come back
+    #[arg(short, long, value_parser = parse_duration, default_value = "60s")]
+    timeout: Duration,
+    /// Passed on to every node, e.g. `-- --no-random-crashes`
+    #[arg(last = true)]
+    node_args: Vec<String>,
+}
+
+struct RestoredNode {
+    name: String,
+    event_log: PathBuf,
+    process: Child,
+}
+
+fn start_node(binary: &str, config_file: &Path, cli: &RestoreCli) -> RestoredNode {
+    let name = config_file
+        .file_stem()
+        .unwrap()
+        .to_string_lossy()
+        .to_string();
+    let event_log = cli.log_dir.join(format!("{name}.events.jsonl"));
+    let log_file = cli.log_dir.join(format!("{name}.log"));
+    let process = Command::new(std::env::current_exe().unwrap().with_file_name(binary))
+        .arg("config")
+        .arg(config_file)
+        .arg("--event-log")
+        .arg(&event_log)
+        .args(&cli.node_args)
+        .stderr(File::create(&log_file).unwrap())
+        .spawn()
+        .unwrap_or_else(|e| panic!("Could not start {binary} for {name}: {e}"));
+    RestoredNode {
+        name,
+        event_log,
+        process,
+    }
+}
+
+/// Time each token was first seen at and how often it was regenerated, in milliseconds since the epoch
+///
+/// Tokens issued by the configs are never regenerated, they count once a thinker got them. The
+/// event logs are appended to, so records of earlier runs are skipped.
+fn restored_tokens(nodes: &[RestoredNode], since_ms: u64) -> BTreeMap<Uuid, (u64, usize)> {
+    let mut tokens = BTreeMap::new();
+    for node in nodes.iter().filter(|node| node.event_log.exists()) {
+        for record in read_event_log(&node.event_log).unwrap_or_default() {
+            let (token, generated) = match record.event {
+                _ if record.wall_time_ms < since_ms => continue,
+                Event::TokenGenerated { token } => (token, 1),
+                Event::GotToken { token, .. } => (token, 0),
+                _ => continue,
+            };
+            let (first_ms, times) = tokens.entry(token.value).or_insert((u64::MAX, 0));
+            *first_ms = (*first_ms).min(record.wall_time_ms);
+            *times += generated;
+        }
+    }
+    tokens
+}
+
+fn main() {
+    init_logger();
+    let cli = RestoreCli::parse();
+    let shutdown = Arc::new(AtomicBool::new(false));
+    for signal in [SIGINT, SIGTERM] {
+        signal_hook::flag::register(signal, shutdown.clone()).unwrap();
+    }
+    let (thinker_configs, fork_configs) = read_config_dir(&cli.config_dir);
+    let tokens = thinker_configs
+        .iter()
+        .flat_map(|config| &config.available_tokens)
+        .map(|token_ref| token_ref.id.value)
+        .collect::<BTreeSet<_>>();
+    fs::create_dir_all(&cli.log_dir).unwrap();
+
+    let started_at = Instant::now();
+    let started_at_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let config_file = |name: String| cli.config_dir.join(format!("{name}.conf"));
+    let mut nodes =
+        fork_configs
+            .iter()
+            .map(|config| start_node("fork", &config_file(Fork::file_name(config)), &cli))
+            .chain(thinker_configs.iter().map(|config| {
+                start_node("thinker", &config_file(Thinker::file_name(config)), &cli)
+            }))
+            .collect::<Vec<_>>();
+    log::info!(
+        "Restored {} thinkers and {} fork replicas from {}, logs in {}",
+        thinker_configs.len(),
+        fork_configs.len(),
+        cli.config_dir.display(),
+        cli.log_dir.display()
+    );
+
+    let mut restored = restored_tokens(&nodes, started_at_ms);
+    while restored.len() < tokens.len()
+        && started_at.elapsed() < cli.timeout
+        && !shutdown.load(Ordering::Relaxed)
+    {
+        sleep(TICK_INTERVAL);
+        restored = restored_tokens(&nodes, started_at_ms);
+    }
+    for token in &tokens {
+        match restored.get(token) {
+            Some((at_ms, times)) => println!(
+                "Token {token} back after {}ms{}",
+                at_ms.saturating_sub(started_at_ms),
+                match times {
+                    0 => ", issued by its config".to_string(),
+                    1 => String::new(),
+                    times => format!(", generated {times} times"),
+                }
+            ),
+            None => println!("Token {token} missing"),
+        }
+    }
+    match restored.values().map(|(at_ms, _)| *at_ms).max() {
+        Some(at_ms) if restored.len() == tokens.len() => println!(
+            "All {} tokens back after {}ms",
+            tokens.len(),
+            at_ms.saturating_sub(started_at_ms)
+        ),
+        _ => println!(
+            "{} of {} tokens back after {:?}",
+            restored.len(),
+            tokens.len(),
+            started_at.elapsed()
+        ),
+    }
+
+    while !shutdown.load(Ordering::Relaxed) {
+        sleep(TICK_INTERVAL);
+    }
+    // Ctrl-C reaches the nodes as well, they shut down on their own
+    let deadline = Instant::now() + SHUTDOWN_GRACE_PERIOD;
+    for node in &mut nodes {
+        while node.process.try_wait().unwrap().is_none() && Instant::now() < deadline {
+            sleep(TICK_INTERVAL);
+        }
+        if node.process.try_wait().unwrap().is_none() {
+            log::warn!("Killing {}", node.name);
+            node.process.kill().unwrap();
+        }
+    }
+}
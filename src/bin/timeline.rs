@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+use philosopher_nom_nom_ring::init_logger;
+use philosopher_nom_nom_ring::lib::events::{Event, EventRecord, read_event_log};
+use uuid::Uuid;
+
+const LANE_HEIGHT: usize = 40;
+const EVENT_WIDTH: usize = 14;
+const LABEL_WIDTH: usize = 120;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Format {
+    Text,
+    /// One JSON record per line, in the merged order
+    Json,
+    /// Space-time diagram with one lane per node
+    Svg,
+}
+
+/// Merges the `--event-log` files of several nodes into one timeline
+///
+/// Nodes started with `--vector-clock` are ordered causally, wall time only breaks ties between concurrent events
+#[derive(Parser, Debug)]
+pub struct TimelineCli {
+    #[arg(required = true)]
+    logs: Vec<PathBuf>,
+    #[arg(short, long, value_enum, default_value = "text")]
+    format: Format,
+    /// Written to stdout if missing
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+fn short_id(node: &Uuid) -> String {
+    node.to_string()[..8].to_string()
+}
+
+/// Whether `before` has to come before `after`, both taken from different nodes
+fn depends_on(after: &EventRecord, before: &EventRecord) -> bool {
+    match (&after.clock, &before.clock) {
+        (Some(after_clock), Some(before_clock)) => {
+            before_clock.get(&before.node) <= after_clock.get(&before.node)
+        }
+        _ => false,
+    }
+}
+
+/// Topological order of the events, picking the earliest ready event by wall time
+fn merge(records: Vec<EventRecord>) -> Vec<EventRecord> {
+    let mut lanes: BTreeMap<Uuid, Vec<EventRecord>> = BTreeMap::new();
+    for record in records {
+        lanes.entry(record.node).or_default().push(record);
+    }
+    // Logs are appended in order, logical times restart with the process
+    for lane in lanes.values_mut() {
+        lane.reverse();
+    }
+
+    let mut merged = vec![];
+    loop {
+        let heads = lanes
+            .values()
+            .filter_map(|lane| lane.last())
+            .collect::<Vec<_>>();
+        if heads.is_empty() {
+            return merged;
+        }
+        let ready = heads
+            .iter()
+            .filter(|record| {
+                heads
+                    .iter()
+                    .all(|other| other.node == record.node || !depends_on(record, other))
+            })
+            .min_by_key(|record| (record.wall_time_ms, record.node));
+        // Clocks restart with the process, so old and new runs in one log can form a cycle
+        let next = ready.or_else(|| {
+            log::warn!("Inconsistent clocks, falling back to wall time");
+            heads
+                .iter()
+                .min_by_key(|record| (record.wall_time_ms, record.node))
+        });
+        let next = next.unwrap().node;
+        merged.push(lanes.get_mut(&next).unwrap().pop().unwrap());
+    }
+}
+
+fn render_text(records: &[EventRecord]) -> String {
+    // Records are in causal order, a skewed host can put an earlier wall time after a later one
+    let start = records
+        .iter()
+        .map(|record| record.wall_time_ms)
+        .min()
+        .unwrap_or_default();
+    let mut output = String::new();
+    for record in records {
+        writeln!(
+            output,
+            "{:>8}ms {} #{:<5} {}",
+            record.wall_time_ms - start,
+            short_id(&record.node),
+            record.logical_time,
+            record.event
+        )
+        .unwrap();
+    }
+    output
+}
+
+fn render_json(records: &[EventRecord]) -> String {
+    records
+        .iter()
+        .map(|record| serde_json::to_string(record).unwrap() + "\n")
+        .collect()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn render_svg(records: &[EventRecord]) -> String {
+    let mut nodes: Vec<(Uuid, &str)> = vec![];
+    for record in records {
+        let kind = match record.event {
+            Event::ThinkerStarted { .. } => Some("thinker"),
+            Event::ForkStarted { .. } => Some("fork"),
+            _ => None,
+        };
+        match nodes.iter_mut().find(|(node, _)| *node == record.node) {
+            Some((_, label)) => *label = kind.unwrap_or(label),
+            None => nodes.push((record.node, kind.unwrap_or("node"))),
+        }
+    }
+    nodes.sort_by_key(|(node, kind)| (kind.to_string(), *node));
+    let lane = |node: &Uuid| nodes.iter().position(|(other, _)| other == node).unwrap();
+    let x = |index: usize| LABEL_WIDTH + EVENT_WIDTH * (index + 1);
+    let y = |lane: usize| LANE_HEIGHT * (lane + 1);
+    let width = x(records.len());
+    let height = y(nodes.len());
+
+    let mut output = String::new();
+    writeln!(
+        output,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" font-family="monospace" font-size="12">"#
+    )
+    .unwrap();
+    writeln!(
+        output,
+        r##"<defs><marker id="arrow" viewBox="0 0 6 6" refX="6" refY="3" markerWidth="6" markerHeight="6" orient="auto"><path d="M0,0 L6,3 L0,6 z" fill="#888"/></marker></defs>"##
+    )
+    .unwrap();
+    for (index, (node, kind)) in nodes.iter().enumerate() {
+        writeln!(
+            output,
+            r##"<text x="4" y="{}" dominant-baseline="middle">{kind} {}</text><line x1="{LABEL_WIDTH}" y1="{0}" x2="{width}" y2="{0}" stroke="#ccc"/>"##,
+            y(index),
+            short_id(node)
+        )
+        .unwrap();
+    }
+
+    // An edge per newly learned dependency, from the last event the sender logged before sending
+    let mut last_seen: BTreeMap<Uuid, BTreeMap<Uuid, usize>> = BTreeMap::new();
+    let mut positions: BTreeMap<Uuid, Vec<usize>> = BTreeMap::new();
+    for (index, record) in records.iter().enumerate() {
+        let seen = last_seen.entry(record.node).or_default();
+        for (other, other_positions) in &positions {
+            if *other == record.node {
+                continue;
+            }
+            let source = other_positions
+                .iter()
+                .rev()
+                .find(|position| depends_on(record, &records[**position]));
+            if let Some(source) = source
+                && seen.get(other) != Some(source)
+            {
+                seen.insert(*other, *source);
+                writeln!(
+                    output,
+                    r##"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="#888" marker-end="url(#arrow)"/>"##,
+                    x(*source),
+                    y(lane(other)),
+                    x(index),
+                    y(lane(&record.node))
+                )
+                .unwrap();
+            }
+        }
+        positions.entry(record.node).or_default().push(index);
+    }
+
+    for (index, record) in records.iter().enumerate() {
+        let color = match record.event.level() {
+            log::Level::Warn => "#e5a50a",
+            _ => "#3465a4",
+        };
+        writeln!(
+            output,
+            r#"<circle cx="{}" cy="{}" r="4" fill="{color}"><title>#{} {}</title></circle>"#,
+            x(index),
+            y(lane(&record.node)),
+            record.logical_time,
+            escape(&record.event.to_string())
+        )
+        .unwrap();
+    }
+    output.push_str("</svg>\n");
+    output
+}
+
+fn main() {
+    init_logger();
+    let cli = TimelineCli::parse();
+
+    let mut records = vec![];
+    for path in &cli.logs {
+        records.extend(read_event_log(path).unwrap_or_else(|e| panic!("{e}")));
+    }
+    if records.iter().any(|record| record.clock.is_none()) {
+        log::warn!("Some events have no vector clock, they are only ordered by wall time");
+    }
+    let records = merge(records);
+    let output = match cli.format {
+        Format::Text => render_text(&records),
+        Format::Json => render_json(&records),
+        Format::Svg => render_svg(&records),
+    };
+    match cli.output {
+        Some(path) => std::fs::write(&path, output)
+            .unwrap_or_else(|e| panic!("Could not write {}: {e}", path.display())),
+        None => print!("{output}"),
+    }
+}
@@ -1,10 +1,22 @@
 use std::{
     net::{SocketAddr, UdpSocket},
+    path::PathBuf,
     thread::sleep,
 };
 
 use clap::Parser;
-use philosopher_nom_nom_ring::lib::{transceiver::Transceiver, visualizer::Visualizer};
+use clap::builder::PossibleValuesParser;
+use philosopher_nom_nom_ring::lib::{
+    config::read_config_dir,
+    runner::serve_metrics,
+    topology::Topology,
+    transceiver::Transceiver,
+    visualizer::{
+        UPDATE_KINDS, Visualizer,
+        trace::{Replay, TraceWriter, parse_speed, read_trace},
+        tui,
+    },
+};
 use philosopher_nom_nom_ring::{NETWORK_BUFFER_SIZE, TICK_INTERVAL};
 use philosopher_nom_nom_ring::{
     init_logger,
@@ -13,42 +25,173 @@ use philosopher_nom_nom_ring::{
 
 #[derive(Parser, Debug)]
 pub struct VisualizerCli {
-    address: SocketAddr,
-    #[arg(short, long)]
-    init_server: SocketAddr,
+    #[arg(required_unless_present = "replay")]
+    address: Option<SocketAddr>,
+    #[arg(short, long, required_unless_present_any = ["replay", "subscribe", "topology"])]
+    init_server: Option<SocketAddr>,
+    /// Joins a running ring instead of waiting for the init server, node addresses are read from a config dir
+    #[arg(long, conflicts_with_all = ["init_server", "replay"])]
+    subscribe: Option<PathBuf>,
+    /// Lays out the ring from a file written with `init --topology` or `init generate` and joins it
+    #[arg(long, conflicts_with_all = ["init_server", "replay", "subscribe"])]
+    topology: Option<PathBuf>,
+    /// Only receive these updates from the nodes, all of them by default
+    #[arg(long, value_delimiter = ',', value_parser = PossibleValuesParser::new(UPDATE_KINDS))]
+    kinds: Vec<String>,
+    /// Full screen terminal UI with node selection and admin actions
+    #[arg(long)]
+    tui: bool,
+    /// Writes every received message with a timestamp and its sender to this file
+    #[arg(long, conflicts_with = "replay")]
+    record: Option<PathBuf>,
+    /// Serves a browser dashboard with a JSON snapshot on `/state` and a server sent events stream on `/events`
+    #[arg(long)]
+    http: Option<SocketAddr>,
+    /// Plays back a file written with `--record` instead of connecting to a ring
+    #[arg(long)]
+    replay: Option<PathBuf>,
+    /// Playback speed of `--replay`
+    #[arg(long, default_value_t = 1.0, requires = "replay", value_parser = parse_speed)]
+    speed: f64,
+    /// Serves Prometheus metrics on `http://<address>/metrics`
+    #[arg(long, conflicts_with = "replay")]
+    metrics: Option<SocketAddr>,
 }
 
 fn main() {
     init_logger();
     let cli = VisualizerCli::parse();
-    let socket = UdpSocket::bind(cli.address).unwrap();
-    let transceiver = Transceiver::new(socket);
-    transceiver.send_reliable(InitMessages::VisualizerRequest, &cli.init_server);
+
+    if let Some(path) = cli.replay {
+        let replay = read_trace(&path)
+            .and_then(|records| Replay::new(records, cli.speed))
+            .unwrap_or_else(|e| panic!("Could not replay {}: {e}", path.display()));
+        replay.run();
+        return;
+    }
+
+    let socket = UdpSocket::bind(cli.address.unwrap()).unwrap();
+    let mut transceiver = Transceiver::new(socket);
+    if let Some(address) = cli.metrics {
+        serve_metrics(transceiver.metrics(), address);
+    }
 
     let mut buffer = [0; NETWORK_BUFFER_SIZE];
+    let mut recorder = cli.record.map(|path| {
+        TraceWriter::create(&path)
+            .unwrap_or_else(|e| panic!("Could not create {}: {e}", path.display()))
+    });
+
+    if let Some(config_dir) = cli.subscribe {
+        let (thinkers, forks) = read_config_dir(&config_dir);
+        if let Some(recorder) = &mut recorder {
+            // Replays and the analyzer start from an empty topology as well
+            let init = VisualizerMessages::Init {
+                thinkers: vec![],
+                forks: vec![],
+            };
+            recorder.write(transceiver.local_address(), init);
+        }
+        let mut visualizer = Visualizer::new(transceiver, vec![], vec![]);
+        visualizer.subscribe(
+            thinkers.iter().map(|config| config.address).collect(),
+            forks.iter().map(|config| config.address).collect(),
+            cli.kinds,
+        );
+        log::info!(
+            "Subscribed to {} thinkers and {} fork replicas",
+            thinkers.len(),
+            forks.len()
+        );
+        run(visualizer, recorder, cli.http, cli.tui, &mut buffer);
+        return;
+    }
+
+    if let Some(path) = cli.topology {
+        let topology = Topology::read(&path).unwrap_or_else(|e| panic!("{e}"));
+        if let Some(recorder) = &mut recorder {
+            recorder.write(transceiver.local_address(), topology.visualizer_init());
+        }
+        let mut visualizer = Visualizer::new(
+            transceiver,
+            topology.thinkers.clone(),
+            topology.forks.clone(),
+        );
+        visualizer.subscribe(
+            topology
+                .thinkers
+                .iter()
+                .map(|thinker| thinker.address)
+                .collect(),
+            topology
+                .forks
+                .iter()
+                .flat_map(|fork| fork.replicas())
+                .copied()
+                .collect(),
+            cli.kinds,
+        );
+        log::info!("Laid out ring from {}", path.display());
+        run(visualizer, recorder, cli.http, cli.tui, &mut buffer);
+        return;
+    }
+
+    transceiver.send_reliable(
+        InitMessages::VisualizerRequest { kinds: cli.kinds },
+        &cli.init_server.unwrap(),
+    );
+
     let mut unhandled_messages = vec![];
 
     let (thinkers, forks) = 'outer: loop {
         log::info!("Waiting for init");
-        while let Some(message) = transceiver.receive::<VisualizerMessages>(&mut buffer) {
+        while let Some((message, entity)) = transceiver.receive::<VisualizerMessages>(&mut buffer) {
             log::info!("Got Message {:#?}", message);
+            let message = match &mut recorder {
+                Some(recorder) => recorder.write(entity, message),
+                None => message,
+            };
             match message {
-                (VisualizerMessages::Init { thinkers, forks }, _) => {
+                VisualizerMessages::Init { thinkers, forks } => {
                     break 'outer (thinkers, forks);
                 }
                 message => {
-                    unhandled_messages.push(message);
+                    unhandled_messages.push((message, entity));
                 }
             }
         }
         sleep(TICK_INTERVAL);
     };
 
-    let mut visualizer = Visualizer::new(transceiver, thinkers, forks);
+    let visualizer = Visualizer::new(transceiver, thinkers, forks);
+    run(visualizer, recorder, cli.http, cli.tui, &mut buffer);
+}
+
+fn run(
+    mut visualizer: Visualizer,
+    recorder: Option<TraceWriter>,
+    http: Option<SocketAddr>,
+    tui: bool,
+    buffer: &mut [u8],
+) {
+    if let Some(recorder) = recorder {
+        visualizer.record(recorder);
+    }
+
+    if let Some(address) = http {
+        let address = visualizer
+            .serve_dashboard(address)
+            .unwrap_or_else(|e| panic!("Could not serve dashboard on {address}: {e}"));
+        log::info!("Serving dashboard on http://{address}");
+    }
 
     log::info!("Started Visualizer");
+    if tui {
+        tui::run(&mut visualizer, buffer).unwrap();
+        return;
+    }
     loop {
-        visualizer.tick(&mut buffer);
+        visualizer.tick(buffer);
         sleep(TICK_INTERVAL);
     }
 }
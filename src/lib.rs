@@ -4,21 +4,41 @@ use std::{sync::LazyLock, time::Duration};
 use rand::Rng;
 
 pub mod lib {
+    pub mod chaos;
+    pub mod clock;
+    pub mod cluster;
     pub mod config;
+    pub mod control;
+    pub mod deployment;
+    #[cfg(feature = "tokio")]
+    pub mod driver;
+    pub mod events;
+    pub mod faults;
     pub mod fork;
+    pub mod http;
     pub mod messages;
+    pub mod metrics;
+    pub mod node;
+    pub mod runner;
     pub mod thinker;
+    pub mod topology;
     pub mod transceiver;
+    pub mod transport;
     pub mod utils;
     pub mod visualizer;
 }
 
-pub const NETWORK_BUFFER_SIZE: usize = 1024;
+/// Largest UDP payload, messages carry vector clocks and grow with the ring
+pub const NETWORK_BUFFER_SIZE: usize = 65507;
 pub const KEEP_MESSAGE_PERCENTAGE: f64 = 0.95;
 
 pub const TICK_INTERVAL: Duration = Duration::from_millis(250);
 pub const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(2);
 pub const KEEP_TOKEN_ALIVE_TIMEOUT: Duration = Duration::from_secs(3);
+pub const FORK_FAILOVER_TIMEOUT: Duration = Duration::from_secs(1);
+/// A primary only confirms its holder while a majority acknowledged it this recently, backups
+/// vote only after `FORK_FAILOVER_TIMEOUT` of silence, so the lease ends before a new election
+pub const FORK_LEASE: Duration = Duration::from_millis(500);
 
 pub const MIN_EATING_TIME: Duration = Duration::from_secs(3);
 pub const MAX_EATING_TIME: Duration = Duration::from_secs(7);
@@ -45,17 +65,18 @@ pub fn init_logger() {
         .init();
 }
 
+#[derive(Debug)]
 pub enum CrashStatus {
     Continue,
-    Crash,
+    Crash(Duration),
     PermanentCrash,
 }
 
-pub fn should_crash() -> CrashStatus {
-    match rand::rng().random_bool(*CRASH_PROBABILITY_PER_TICK) {
-        true => match rand::rng().random_bool(PERMANET_CRASH_PERCENTAGE) {
+pub fn should_crash(rng: &mut impl Rng) -> CrashStatus {
+    match rng.random_bool(*CRASH_PROBABILITY_PER_TICK) {
+        true => match rng.random_bool(PERMANET_CRASH_PERCENTAGE) {
             true => CrashStatus::PermanentCrash,
-            false => CrashStatus::Crash,
+            false => CrashStatus::Crash(rng.random_range(MIN_CRASH_DURATION..=MAX_CRASH_DURATION)),
         },
         false => CrashStatus::Continue,
     }
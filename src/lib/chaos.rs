@@ -0,0 +1,468 @@
+use std::io::Write;
+use std::net::{SocketAddr, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use uuid::Uuid;
+
+use crate::lib::config::{ThinkerConfig, read_config_dir};
+use crate::lib::events::{Event, EventRecord, read_event_log};
+use crate::lib::faults::parse_percentage;
+use crate::lib::messages::{ControlMessage, ForkMessages, ThinkerMessage};
+use crate::lib::transceiver::Transceiver;
+use crate::lib::utils::parse_duration;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NodeRef {
+    Thinker(usize),
+    /// All replicas of the fork
+    Fork(usize),
+    /// One replica of a replicated fork, by the rank it started with
+    Replica {
+        fork: usize,
+        rank: usize,
+    },
+}
+
+impl std::fmt::Display for NodeRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NodeRef::Thinker(index) => write!(f, "thinker {index}"),
+            NodeRef::Fork(index) => write!(f, "fork {index}"),
+            NodeRef::Replica { fork, rank } => write!(f, "fork {fork}.{rank}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ChaosAction {
+    Crash {
+        node: NodeRef,
+        /// `None` crashes permanently
+        duration: Option<Duration>,
+    },
+    Pause(NodeRef),
+    Resume(NodeRef),
+    Drop {
+        node: NodeRef,
+        percentage: f64,
+    },
+    RandomCrashes(bool),
+    Partition {
+        name: String,
+        sides: [Vec<NodeRef>; 2],
+    },
+    Heal {
+        name: Option<String>,
+    },
+}
+
+impl std::fmt::Display for ChaosAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let nodes = |nodes: &Vec<NodeRef>| {
+            nodes
+                .iter()
+                .map(|node| node.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            ChaosAction::Crash {
+                node,
+                duration: Some(duration),
+            } => write!(f, "crash {node} for {duration:?}"),
+            ChaosAction::Crash {
+                node,
+                duration: None,
+            } => write!(f, "crash {node} permanently"),
+            ChaosAction::Pause(node) => write!(f, "pause {node}"),
+            ChaosAction::Resume(node) => write!(f, "resume {node}"),
+            ChaosAction::Drop { node, percentage } => write!(f, "drop {percentage} of {node}"),
+            ChaosAction::RandomCrashes(enabled) => write!(f, "random crashes {enabled}"),
+            ChaosAction::Partition { name, sides } => write!(
+                f,
+                "partition {name} {{{}}} from {{{}}}",
+                nodes(&sides[0]),
+                nodes(&sides[1])
+            ),
+            ChaosAction::Heal { name: Some(name) } => write!(f, "heal {name}"),
+            ChaosAction::Heal { name: None } => write!(f, "heal"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScenarioStep {
+    pub at: Duration,
+    pub action: ChaosAction,
+}
+
+/// A fault scenario, one step per line or separated by `;`:
+///
+/// ```text
+/// t=10s crash thinker 3 for 8s
+/// t=12s crash fork 1 permanently
+/// t=15s pause t0; t=18s resume t0
+/// t=16s crash f2.0 for 5s
+/// t=20s drop thinker 2 0.5
+/// t=20s partition west {0,1,2} from {3,4,f0}
+/// t=40s heal
+/// ```
+///
+/// Nodes are counted along the ring, bare numbers in partitions are thinkers. `f2.0` is the
+/// replica of fork 2 with rank 0.
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub steps: Vec<ScenarioStep>,
+}
+
+fn parse_node(words: &mut std::slice::Iter<&str>) -> Result<NodeRef, String> {
+    let word = words.next().ok_or("Expected a node")?;
+    let (role, index) = match *word {
+        "thinker" | "fork" => (*word, *words.next().ok_or("Expected a node index")?),
+        _ if word.starts_with('t') => ("thinker", &word[1..]),
+        _ if word.starts_with('f') => ("fork", &word[1..]),
+        _ => ("thinker", *word),
+    };
+    let parse = |index: &str| {
+        index
+            .parse::<usize>()
+            .map_err(|_| format!("Invalid node index {index}"))
+    };
+    Ok(match (role, index.split_once('.')) {
+        ("fork", Some((fork, rank))) => NodeRef::Replica {
+            fork: parse(fork)?,
+            rank: parse(rank)?,
+        },
+        ("thinker", None) => NodeRef::Thinker(parse(index)?),
+        ("fork", None) => NodeRef::Fork(parse(index)?),
+        _ => Err(format!("Only forks have replicas, got {word} {index}"))?,
+    })
+}
+
+fn parse_node_set(set: &str) -> Result<Vec<NodeRef>, String> {
+    set.split(',')
+        .map(str::trim)
+        .filter(|node| !node.is_empty())
+        .map(|node| parse_node(&mut [node].iter()))
+        .collect()
+}
+
+fn parse_partition(rest: &str) -> Result<ChaosAction, String> {
+    let open = rest.find('{').ok_or("Partition is missing {")?;
+    let name = match rest[..open].trim() {
+        "" => "default".to_string(),
+        name => name.to_string(),
+    };
+    let sets = rest[open..]
+        .split('}')
+        .filter_map(|part| part.split_once('{').map(|(_, set)| set))
+        .map(parse_node_set)
+        .collect::<Result<Vec<_>, _>>()?;
+    let sides: [Vec<NodeRef>; 2] = sets
+        .try_into()
+        .map_err(|_| "Partition needs exactly two sets")?;
+    Ok(ChaosAction::Partition { name, sides })
+}
+
+fn parse_step(step: &str) -> Result<ScenarioStep, String> {
+    let step = step.trim().trim_start_matches("t=");
+    let (at, rest) = step
+        .split_once(char::is_whitespace)
+        .ok_or_else(|| format!("Step {step} has no action"))?;
+    let at = parse_duration(at)?;
+    let words = rest.split_whitespace().collect::<Vec<_>>();
+    let mut words_iter = words.iter();
+    let action = match *words_iter.next().ok_or("Expected an action")? {
+        "crash" => {
+            let node = parse_node(&mut words_iter)?;
+            let duration = match words_iter.as_slice() {
+                ["for", duration] => Some(parse_duration(duration)?),
+                ["permanently"] => None,
+                _ => Err(format!(
+                    "Expected `for <duration>` or `permanently` in {step}"
+                ))?,
+            };
+            ChaosAction::Crash { node, duration }
+        }
+        "pause" => ChaosAction::Pause(parse_node(&mut words_iter)?),
+        "resume" => ChaosAction::Resume(parse_node(&mut words_iter)?),
+        "drop" => {
+            let node = parse_node(&mut words_iter)?;
+            let percentage = words_iter
+                .next()
+                .ok_or_else(|| format!("Expected a drop percentage in {step}"))
+                .and_then(|percentage| parse_percentage(percentage))?;
+            ChaosAction::Drop { node, percentage }
+        }
+        "random-crashes" => match words_iter.as_slice() {
+            ["on"] => ChaosAction::RandomCrashes(true),
+            ["off"] => ChaosAction::RandomCrashes(false),
+            _ => Err(format!("Expected on or off in {step}"))?,
+        },
+        "partition" => parse_partition(rest.trim_start().trim_start_matches("partition"))?,
+        "heal" => ChaosAction::Heal {
+            name: words_iter.next().map(|name| name.to_string()),
+        },
+        action => Err(format!("Unknown action {action}"))?,
+    };
+    Ok(ScenarioStep { at, action })
+}
+
+impl Scenario {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut steps = source
+            .lines()
+            .map(|line| line.split('#').next().unwrap())
+            .flat_map(|line| line.split(';'))
+            .filter(|step| !step.trim().is_empty())
+            .map(parse_step)
+            .collect::<Result<Vec<_>, _>>()?;
+        steps.sort_by_key(|step| step.at);
+        Ok(Self { steps })
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+        Self::parse(&source)
+    }
+}
+
+/// Cluster a scenario can be executed against
+pub trait ChaosTarget {
+    fn nodes(&self) -> Vec<NodeRef>;
+    /// All addresses of a node, replicated forks have more than one
+    fn addresses(&self, node: NodeRef) -> Vec<SocketAddr>;
+    fn send_control(&mut self, node: NodeRef, message: ControlMessage);
+    /// Blocks or steps the cluster until the scenario time `at` is reached
+    fn wait_until(&mut self, at: Duration);
+    /// Events the nodes logged since the last call
+    fn take_events(&mut self) -> Vec<EventRecord>;
+}
+
+impl ChaosAction {
+    pub fn execute(&self, target: &mut impl ChaosTarget) {
+        match self {
+            ChaosAction::Crash { node, duration } => target.send_control(
+                *node,
+                match duration {
+                    Some(duration) => ControlMessage::Crash {
+                        duration: *duration,
+                    },
+                    None => ControlMessage::CrashPermanently,
+                },
+            ),
+            ChaosAction::Pause(node) => target.send_control(*node, ControlMessage::Pause),
+            ChaosAction::Resume(node) => target.send_control(*node, ControlMessage::Resume),
+            ChaosAction::Drop { node, percentage } => target.send_control(
+                *node,
+                ControlMessage::DropMessages {
+                    percentage: *percentage,
+                },
+            ),
+            ChaosAction::RandomCrashes(enabled) => target.nodes().into_iter().for_each(|node| {
+                target.send_control(node, ControlMessage::RandomCrashes(*enabled))
+            }),
+            ChaosAction::Partition { name, sides } => {
+                let addresses = |side: &Vec<NodeRef>| {
+                    side.iter()
+                        .flat_map(|node| target.addresses(*node))
+                        .collect::<Vec<_>>()
+                };
+                let blocked = [addresses(&sides[1]), addresses(&sides[0])];
+                for (side, blocked) in sides.iter().zip(blocked) {
+                    side.iter().for_each(|node| {
+                        target.send_control(
+                            *node,
+                            ControlMessage::Partition {
+                                name: name.clone(),
+                                blocked: blocked.clone(),
+                            },
+                        )
+                    });
+                }
+            }
+            ChaosAction::Heal { name } => target.nodes().into_iter().for_each(|node| {
+                target.send_control(node, ControlMessage::Heal { name: name.clone() })
+            }),
+        }
+    }
+}
+
+fn write_records(trace: &mut impl Write, records: impl IntoIterator<Item = EventRecord>) {
+    for record in records {
+        writeln!(trace, "{}", serde_json::to_string(&record).unwrap()).unwrap();
+    }
+}
+
+/// Executes all steps and keeps the cluster running for `settle` after the last one
+///
+/// The trace gets the events of the cluster as NDJSON, like an event log that `timeline` reads.
+/// Every step is recorded in between as a `fault_injected` event of the nil node.
+pub fn run_scenario(
+    scenario: &Scenario,
+    target: &mut impl ChaosTarget,
+    settle: Duration,
+    trace: &mut impl Write,
+) {
+    for (index, step) in scenario.steps.iter().enumerate() {
+        target.wait_until(step.at);
+        log::info!("[{:?}] {}", step.at, step.action);
+        step.action.execute(target);
+        let mut records = target.take_events();
+        records.sort_by_key(|record| record.wall_time_ms);
+        records.push(EventRecord {
+            node: Uuid::nil(),
+            wall_time_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            logical_time: index as u64 + 1,
+            clock: None,
+            event: Event::FaultInjected {
+                action: step.action.to_string(),
+            },
+        });
+        write_records(trace, records);
+    }
+    let end = scenario
+        .steps
+        .last()
+        .map(|step| step.at)
+        .unwrap_or_default()
+        + settle;
+    target.wait_until(end);
+    let mut records = target.take_events();
+    records.sort_by_key(|record| record.wall_time_ms);
+    write_records(trace, records);
+    trace.flush().unwrap();
+}
+
+/// Cluster of `thinker`/`fork` processes reached over UDP through their saved configs
+pub struct UdpChaosTarget {
+    transceiver: Transceiver,
+    started_at: Instant,
+    thinkers: Vec<SocketAddr>,
+    forks: Vec<Vec<SocketAddr>>,
+    /// `--event-log` files of the nodes and how many records of each were already taken
+    event_logs: Vec<(PathBuf, usize)>,
+}
+
+impl UdpChaosTarget {
+    /// Orders the nodes along the ring, starting at the thinker with the smallest id
+    pub fn from_config_dir(dir: &Path) -> Self {
+        let (thinker_configs, fork_configs) = read_config_dir(dir);
+        let mut ring: Vec<&ThinkerConfig> = vec![];
+        if let Some(first) = thinker_configs.iter().min_by_key(|config| config.id.value) {
+            let mut current = first;
+            while !ring.iter().any(|config| config.id.eq(&current.id)) {
+                ring.push(current);
+                let next_id = &current.next_thinkers[0].id;
+                match thinker_configs.iter().find(|config| config.id.eq(next_id)) {
+                    Some(next) => current = next,
+                    None => break,
+                }
+            }
+        }
+        if ring.len() != thinker_configs.len() {
+            log::warn!(
+                "Only {} of {} thinker configs form a ring",
+                ring.len(),
+                thinker_configs.len()
+            );
+        }
+        let forks = ring
+            .iter()
+            .map(|thinker| {
+                let fork_id = &thinker.forks[0].id;
+                let mut replicas = fork_configs
+                    .iter()
+                    .filter(|config| config.id.eq(fork_id))
+                    .collect::<Vec<_>>();
+                replicas.sort_by_key(|config| config.rank);
+                replicas.iter().map(|config| config.address).collect()
+            })
+            .collect();
+        Self {
+            transceiver: Transceiver::new(UdpSocket::bind("0.0.0.0:0").unwrap()),
+            started_at: Instant::now(),
+            thinkers: ring.iter().map(|config| config.address).collect(),
+            forks,
+            event_logs: vec![],
+        }
+    }
+
+    /// The trace gets what is appended to these files while the scenario runs
+    pub fn with_event_logs(mut self, paths: Vec<PathBuf>) -> Self {
+        self.event_logs = paths
+            .into_iter()
+            .map(|path| {
+                let taken = read_event_log(&path).map(|records| records.len());
+                (path, taken.unwrap_or_default())
+            })
+            .collect();
+        self
+    }
+}
+
+impl ChaosTarget for UdpChaosTarget {
+    fn nodes(&self) -> Vec<NodeRef> {
+        (0..self.thinkers.len())
+            .map(NodeRef::Thinker)
+            .chain((0..self.forks.len()).map(NodeRef::Fork))
+            .collect()
+    }
+
+    fn addresses(&self, node: NodeRef) -> Vec<SocketAddr> {
+        match node {
+            NodeRef::Thinker(index) => self.thinkers.get(index).into_iter().copied().collect(),
+            NodeRef::Fork(index) => self.forks.get(index).cloned().unwrap_or_default(),
+            NodeRef::Replica { fork, rank } => self
+                .forks
+                .get(fork)
+                .and_then(|replicas| replicas.get(rank))
+                .into_iter()
+                .copied()
+                .collect(),
+        }
+    }
+
+    fn send_control(&mut self, node: NodeRef, message: ControlMessage) {
+        let addresses = self.addresses(node);
+        if addresses.is_empty() {
+            log::error!("Scenario references unknown {node}");
+        }
+        for address in addresses {
+            match node {
+                NodeRef::Thinker(_) => self
+                    .transceiver
+                    .send_reliable(ThinkerMessage::Control(message.clone()), &address),
+                NodeRef::Fork(_) | NodeRef::Replica { .. } => self
+                    .transceiver
+                    .send_reliable(ForkMessages::Control(message.clone()), &address),
+            }
+        }
+    }
+
+    fn wait_until(&mut self, at: Duration) {
+        if let Some(remaining) = at.checked_sub(self.started_at.elapsed()) {
+            sleep(remaining);
+        }
+    }
+
+    fn take_events(&mut self) -> Vec<EventRecord> {
+        let mut records = vec![];
+        for (path, taken) in &mut self.event_logs {
+            // Missing while the node has not written its first event yet
+            let Ok(log) = read_event_log(path) else {
+                continue;
+            };
+            let len = log.len();
+            records.extend(log.into_iter().skip(*taken));
+            *taken = len.max(*taken);
+        }
+        records
+    }
+}
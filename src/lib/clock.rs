@@ -0,0 +1,96 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use rkyv::{Archive, Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Counter per node, node ids are the uuids of thinkers and forks
+#[derive(
+    Archive,
+    Serialize,
+    Deserialize,
+    serde::Serialize,
+    serde::Deserialize,
+    Debug,
+    Clone,
+    Default,
+    PartialEq,
+    Eq,
+)]
+#[serde(transparent)]
+pub struct VectorClock {
+    pub entries: BTreeMap<Uuid, u64>,
+}
+
+impl VectorClock {
+    pub fn get(&self, node: &Uuid) -> u64 {
+        self.entries.get(node).copied().unwrap_or_default()
+    }
+
+    pub fn merge(&mut self, other: &VectorClock) {
+        for (node, counter) in &other.entries {
+            let entry = self.entries.entry(*node).or_default();
+            *entry = (*entry).max(*counter);
+        }
+    }
+
+    /// Strict happened-before
+    pub fn happened_before(&self, other: &VectorClock) -> bool {
+        self != other
+            && self
+                .entries
+                .iter()
+                .all(|(node, counter)| *counter <= other.get(node))
+    }
+
+    /// Grows with every step along a causal chain, sorting by it yields a causally consistent order
+    pub fn sum(&self) -> u64 {
+        self.entries.values().sum()
+    }
+}
+
+#[derive(Debug)]
+struct NodeClock {
+    node: Uuid,
+    clock: VectorClock,
+}
+
+/// Clock of one node, shared by its transceiver and its event log
+#[derive(Debug, Clone)]
+pub struct SharedClock {
+    inner: Arc<Mutex<NodeClock>>,
+}
+
+impl SharedClock {
+    pub fn new(node: Uuid) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(NodeClock {
+                node,
+                clock: VectorClock::default(),
+            })),
+        }
+    }
+
+    /// Nodes learn their final id during init, the counters so far are moved over
+    pub fn set_node(&self, node: Uuid) {
+        let mut inner = self.inner.lock().unwrap();
+        let previous = inner.node;
+        if let Some(counter) = inner.clock.entries.remove(&previous) {
+            inner.clock.entries.insert(node, counter);
+        }
+        inner.node = node;
+    }
+
+    /// Counts a local event or a send and returns the new clock
+    pub fn tick(&self) -> VectorClock {
+        let mut inner = self.inner.lock().unwrap();
+        let node = inner.node;
+        *inner.clock.entries.entry(node).or_default() += 1;
+        inner.clock.clone()
+    }
+
+    pub fn receive(&self, clock: &VectorClock) {
+        self.inner.lock().unwrap().clock.merge(clock);
+        self.tick();
+    }
+}
@@ -0,0 +1,426 @@
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use crate::lib::chaos::{ChaosTarget, NodeRef};
+use crate::lib::control::NodeControl;
+use crate::lib::events::{EventLog, EventRecord, EventSink};
+use crate::lib::faults::FaultConfig;
+use crate::lib::fork::{Fork, ForkInitParams, ForkRef};
+use crate::lib::messages::visualizer_messages::{VisualizerForkState, VisualizerThinkerState};
+use crate::lib::messages::{ControlMessage, ForkMessages, ThinkerMessage};
+use crate::lib::node::Node;
+use crate::lib::thinker::{MealTiming, Thinker, ThinkerInitParams, ThinkerRef};
+use crate::lib::topology::Topology;
+use crate::lib::transceiver::Transceiver;
+use crate::lib::transport::MemoryNetwork;
+use crate::lib::utils::Id;
+use crate::{CrashStatus, NETWORK_BUFFER_SIZE};
+
+/// Pause between two steps of the whole cluster in `Cluster::run_for`
+const STEP_INTERVAL: Duration = Duration::from_millis(1);
+
+/// What the nodes of a cluster talk over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClusterTransport {
+    /// Sockets on 127.0.0.1, so `ctl` and visualizers can reach the nodes
+    Udp,
+    /// In-process mailboxes, independent of the host network
+    #[default]
+    Memory,
+}
+
+/// Stands up a ring in this process, without init server and node binaries
+#[derive(Debug, Clone)]
+pub struct ClusterBuilder {
+    thinkers: usize,
+    next_thinkers_amount: usize,
+    tokens: usize,
+    fork_replicas: usize,
+    timing: MealTiming,
+    transport: ClusterTransport,
+    fault_config: FaultConfig,
+    random_crashes: bool,
+    seed: Option<u64>,
+}
+
+impl ClusterBuilder {
+    /// Two successors, one token, unreplicated forks and no random crashes
+    pub fn new(thinkers: usize) -> Self {
+        Self {
+            thinkers,
+            next_thinkers_amount: 2,
+            tokens: 1,
+            fork_replicas: 1,
+            timing: MealTiming::default(),
+            transport: ClusterTransport::default(),
+            fault_config: FaultConfig::default(),
+            random_crashes: false,
+            seed: None,
+        }
+    }
+
+    pub fn next_thinkers_amount(mut self, next_thinkers_amount: usize) -> Self {
+        self.next_thinkers_amount = next_thinkers_amount;
+        self
+    }
+
+    pub fn tokens(mut self, tokens: usize) -> Self {
+        self.tokens = tokens;
+        self
+    }
+
+    pub fn fork_replicas(mut self, fork_replicas: usize) -> Self {
+        self.fork_replicas = fork_replicas;
+        self
+    }
+
+    /// Timeouts stay the crate constants, they are tied to `TICK_INTERVAL`
+    pub fn timing(mut self, timing: MealTiming) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    pub fn transport(mut self, transport: ClusterTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Applied to every node
+    pub fn fault_config(mut self, fault_config: FaultConfig) -> Self {
+        self.fault_config = fault_config;
+        self
+    }
+
+    pub fn random_crashes(mut self, random_crashes: bool) -> Self {
+        self.random_crashes = random_crashes;
+        self
+    }
+
+    /// Reproducible ids, meal timing and random crashes, see `Topology::with_seed`
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn build(self) -> Cluster {
+        let network = MemoryNetwork::new();
+        let bind = || match self.transport {
+            ClusterTransport::Udp => Transceiver::new(UdpSocket::bind("127.0.0.1:0").unwrap()),
+            ClusterTransport::Memory => Transceiver::with_transport(Box::new(network.bind())),
+        };
+        let thinker_transceivers = (0..self.thinkers).map(|_| bind()).collect::<Vec<_>>();
+        let fork_transceivers = (0..self.thinkers * self.fork_replicas)
+            .map(|_| bind())
+            .collect::<Vec<_>>();
+        let mut topology = Topology::ring(
+            thinker_transceivers
+                .iter()
+                .map(|transceiver| ThinkerRef {
+                    address: transceiver.local_address(),
+                    id: Id::random(),
+                })
+                .collect(),
+            fork_transceivers
+                .iter()
+                .map(|transceiver| ForkRef {
+                    address: transceiver.local_address(),
+                    id: Id::random(),
+                    backups: vec![],
+                })
+                .collect(),
+            self.tokens,
+            vec![],
+            self.next_thinkers_amount,
+            self.fork_replicas,
+        );
+        if let Some(seed) = self.seed {
+            topology = topology.with_seed(seed);
+        }
+
+        let events = Arc::new(Mutex::new(vec![]));
+        let event_log = || {
+            let events = events.clone();
+            let mut event_log = EventLog::default();
+            event_log.add_sink(EventSink::new(move |record: &EventRecord| {
+                events.lock().unwrap().push(record.clone());
+            }));
+            event_log
+        };
+        let thinkers = thinker_transceivers
+            .into_iter()
+            .enumerate()
+            .map(|(index, mut transceiver)| {
+                transceiver.set_fault_config(self.fault_config.clone());
+                let init_params = topology.thinker_init(index);
+                ClusterNode::new(Thinker::new(ThinkerInitParams {
+                    id: init_params.id,
+                    transceiver,
+                    unhandled_messages: vec![],
+                    forks: init_params.forks,
+                    next_thinkers: init_params.next_thinkers,
+                    previous_thinkers: init_params.previous_thinkers,
+                    token: init_params.token,
+                    available_tokens: init_params.available_tokens,
+                    visualizers: init_params.visualizers,
+                    checkpoint: None,
+                    checkpoint_file: None,
+                    control: NodeControl::new(self.random_crashes),
+                    event_log: event_log(),
+                    timing: self.timing.clone(),
+                    seed: init_params.seed,
+                }))
+            })
+            .collect();
+        // Both are ordered by fork and then by rank
+        let mut replicas = fork_transceivers
+            .into_iter()
+            .zip(topology.fork_inits())
+            .map(|(mut transceiver, (_, init_params))| {
+                transceiver.set_fault_config(self.fault_config.clone());
+                ClusterNode::new(Fork::new(ForkInitParams {
+                    id: init_params.id,
+                    transceiver,
+                    visualizers: init_params.visualizers,
+                    unhandled_messages: vec![],
+                    replicas: init_params.replicas,
+                    rank: init_params.rank,
+                    checkpoint: None,
+                    checkpoint_file: None,
+                    control: NodeControl::new(self.random_crashes),
+                    event_log: event_log(),
+                    seed: init_params.seed,
+                }))
+            });
+        let forks = (0..topology.forks.len())
+            .map(|_| replicas.by_ref().take(self.fork_replicas).collect())
+            .collect();
+
+        Cluster {
+            thinkers,
+            forks,
+            topology,
+            control: bind(),
+            events,
+            started_at: Instant::now(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeStatus {
+    Running,
+    Crashed { restart_at: Instant },
+    PermanentlyCrashed,
+}
+
+/// Node of a cluster together with its simulated process
+#[derive(Debug)]
+pub struct ClusterNode<N> {
+    /// Only empty while the node is reset
+    node: Option<N>,
+    status: NodeStatus,
+}
+
+impl<N: Node> ClusterNode<N> {
+    fn new(mut node: N) -> Self {
+        node.print_started();
+        Self {
+            node: Some(node),
+            status: NodeStatus::Running,
+        }
+    }
+
+    pub fn node(&self) -> &N {
+        self.node.as_ref().unwrap()
+    }
+
+    pub fn status(&self) -> NodeStatus {
+        self.status
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.status == NodeStatus::Running
+    }
+
+    /// Same as one iteration of `runner::run_node`, except that crashes do not block
+    fn step(&mut self, buffer: &mut [u8]) {
+        match self.status {
+            NodeStatus::Running => {
+                let node = self.node.as_mut().unwrap();
+                node.tick(buffer);
+                node.update_visualizer();
+                match node.should_crash() {
+                    CrashStatus::Continue => (),
+                    CrashStatus::Crash(crash_duration) => {
+                        log::info!("{} crashed. Restarting in {:?}", node.id(), crash_duration);
+                        self.status = NodeStatus::Crashed {
+                            restart_at: Instant::now() + crash_duration,
+                        };
+                    }
+                    CrashStatus::PermanentCrash => {
+                        log::error!("{} permanently crashed", node.id());
+                        self.status = NodeStatus::PermanentlyCrashed;
+                    }
+                }
+            }
+            NodeStatus::Crashed { restart_at } if restart_at <= Instant::now() => {
+                let mut node = self.node.take().unwrap().reset();
+                node.print_started();
+                self.node = Some(node);
+                self.status = NodeStatus::Running;
+            }
+            NodeStatus::Crashed { .. } | NodeStatus::PermanentlyCrashed => (),
+        }
+    }
+}
+
+/// Ring built by `ClusterBuilder`, stepped by the caller. Thinker `i` sits between fork `i` and
+/// fork `i + 1`, the same order `NodeRef` uses in chaos scenarios.
+#[derive(Debug)]
+pub struct Cluster {
+    thinkers: Vec<ClusterNode<Thinker>>,
+    /// Replicas of every fork ordered by rank
+    forks: Vec<Vec<ClusterNode<Fork>>>,
+    topology: Topology,
+    /// Sends the control messages of injected faults
+    control: Transceiver,
+    events: Arc<Mutex<Vec<EventRecord>>>,
+    started_at: Instant,
+}
+
+impl Cluster {
+    pub fn topology(&self) -> &Topology {
+        &self.topology
+    }
+
+    pub fn thinker(&self, index: usize) -> &ClusterNode<Thinker> {
+        &self.thinkers[index]
+    }
+
+    pub fn fork_replicas(&self, index: usize) -> &[ClusterNode<Fork>] {
+        &self.forks[index]
+    }
+
+    /// `None` for crashed thinkers
+    pub fn thinker_states(&self) -> Vec<Option<VisualizerThinkerState>> {
+        self.thinkers
+            .iter()
+            .map(|thinker| thinker.is_running().then(|| thinker.node().state()))
+            .collect()
+    }
+
+    /// State of the running primary of every fork, `None` while there is none
+    pub fn fork_states(&self) -> Vec<Option<VisualizerForkState>> {
+        self.forks
+            .iter()
+            .map(|replicas| {
+                replicas
+                    .iter()
+                    .find(|replica| replica.is_running() && replica.node().is_primary())
+                    .map(|replica| replica.node().state())
+            })
+            .collect()
+    }
+
+    /// Steps every node once
+    pub fn step(&mut self) {
+        let mut buffer = [0; NETWORK_BUFFER_SIZE];
+        self.thinkers
+            .iter_mut()
+            .for_each(|thinker| thinker.step(&mut buffer));
+        self.forks
+            .iter_mut()
+            .flatten()
+            .for_each(|replica| replica.step(&mut buffer));
+    }
+
+    pub fn run_for(&mut self, duration: Duration) {
+        let until = Instant::now() + duration;
+        while Instant::now() < until {
+            self.step();
+            sleep(STEP_INTERVAL);
+        }
+    }
+
+    /// Steps until `condition` holds, false if it did not within `timeout`
+    pub fn run_until(
+        &mut self,
+        timeout: Duration,
+        mut condition: impl FnMut(&Self) -> bool,
+    ) -> bool {
+        let until = Instant::now() + timeout;
+        while !condition(self) {
+            if Instant::now() >= until {
+                return false;
+            }
+            self.step();
+            sleep(STEP_INTERVAL);
+        }
+        true
+    }
+
+    /// Events of all nodes since the last call, in the order they were logged
+    pub fn take_events(&mut self) -> Vec<EventRecord> {
+        std::mem::take(&mut self.events.lock().unwrap())
+    }
+}
+
+impl ChaosTarget for Cluster {
+    fn nodes(&self) -> Vec<NodeRef> {
+        (0..self.thinkers.len())
+            .map(NodeRef::Thinker)
+            .chain((0..self.forks.len()).map(NodeRef::Fork))
+            .collect()
+    }
+
+    fn addresses(&self, node: NodeRef) -> Vec<SocketAddr> {
+        match node {
+            NodeRef::Thinker(index) => self
+                .topology
+                .thinkers
+                .get(index)
+                .map(|thinker| thinker.address)
+                .into_iter()
+                .collect(),
+            NodeRef::Fork(index) => self
+                .topology
+                .forks
+                .get(index)
+                .map(|fork| fork.replicas().copied().collect())
+                .unwrap_or_default(),
+            NodeRef::Replica { fork, rank } => self
+                .topology
+                .forks
+                .get(fork)
+                .and_then(|fork| fork.replicas().nth(rank))
+                .into_iter()
+                .copied()
+                .collect(),
+        }
+    }
+
+    fn send_control(&mut self, node: NodeRef, message: ControlMessage) {
+        for address in self.addresses(node) {
+            match node {
+                NodeRef::Thinker(_) => self
+                    .control
+                    .send_reliable(ThinkerMessage::Control(message.clone()), &address),
+                NodeRef::Fork(_) | NodeRef::Replica { .. } => self
+                    .control
+                    .send_reliable(ForkMessages::Control(message.clone()), &address),
+            }
+        }
+    }
+
+    fn wait_until(&mut self, at: Duration) {
+        if let Some(remaining) = at.checked_sub(self.started_at.elapsed()) {
+            self.run_for(remaining);
+        }
+    }
+
+    fn take_events(&mut self) -> Vec<EventRecord> {
+        Cluster::take_events(self)
+    }
+}
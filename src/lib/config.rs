@@ -1,6 +1,7 @@
 use std::{
-    fs::File,
+    fs::{self, File},
     io::{Read, Write},
+    net::SocketAddr,
     path::Path,
 };
 
@@ -14,9 +15,17 @@ use rkyv::{
     util::AlignedVec,
 };
 
+use crate::lib::fork::{Fork, ForkRef};
+use crate::lib::messages::thinker_messages::{Token, TokenRef};
+use crate::lib::thinker::{Thinker, ThinkerRef};
+use crate::lib::utils::Id;
+use crate::lib::visualizer::VisualizerRef;
+
 pub trait Config {
     fn write(&self, path: &Path);
     fn read(path: &Path) -> Self;
+    /// Writes to a temporary file first and renames it, so a crash never leaves a torn file behind
+    fn write_atomic(&self, path: &Path);
 }
 
 impl<T> Config for T
@@ -33,6 +42,15 @@ where
         file.write_all(&message_bytes).unwrap();
     }
 
+    fn write_atomic(&self, path: &Path) {
+        let temporary_path = path.with_extension("tmp");
+        let mut file = File::create(&temporary_path).unwrap();
+        let message_bytes = rkyv::to_bytes::<rkyv::rancor::Error>(self).unwrap();
+        file.write_all(&message_bytes).unwrap();
+        file.sync_all().unwrap();
+        fs::rename(&temporary_path, path).unwrap();
+    }
+
     fn read(path: &Path) -> Self {
         let mut file = File::open(path).unwrap();
         let mut buffer = Vec::new();
@@ -40,3 +58,52 @@ where
         rkyv::from_bytes::<T, rkyv::rancor::Error>(&buffer).unwrap()
     }
 }
+
+#[derive(Debug, Serialize, Deserialize, Archive)]
+pub struct ThinkerConfig {
+    pub id: Id<Thinker>,
+    pub address: SocketAddr,
+    pub visualizers: Vec<VisualizerRef>,
+    pub forks: [ForkRef; 2],
+    pub next_thinkers: Vec<ThinkerRef>,
+    pub previous_thinkers: Vec<ThinkerRef>,
+    /// Issued on the first start, a thinker that already ran leaves it to the ring to regenerate
+    pub token: Option<Token>,
+    pub available_tokens: Vec<TokenRef>,
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Archive)]
+pub struct ForkConfig {
+    pub id: Id<Fork>,
+    pub address: SocketAddr,
+    pub visualizers: Vec<VisualizerRef>,
+    pub replicas: Vec<SocketAddr>,
+    pub rank: usize,
+    pub seed: Option<u64>,
+}
+
+/// Reads every `thinker_*.conf` and `fork_*.conf` of a directory written with `--save-config-dir`
+pub fn read_config_dir(dir: &Path) -> (Vec<ThinkerConfig>, Vec<ForkConfig>) {
+    let mut paths = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "conf")
+        })
+        .collect::<Vec<_>>();
+    paths.sort();
+    let file_name = |path: &Path| path.file_name().unwrap().to_string_lossy().to_string();
+    let thinkers = paths
+        .iter()
+        .filter(|path| file_name(path).starts_with("thinker_"))
+        .map(|path| ThinkerConfig::read(path))
+        .collect();
+    let forks = paths
+        .iter()
+        .filter(|path| file_name(path).starts_with("fork_"))
+        .map(|path| ForkConfig::read(path))
+        .collect();
+    (thinkers, forks)
+}
@@ -0,0 +1,68 @@
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+use crate::lib::messages::ControlMessage;
+use crate::lib::runner::Heartbeat;
+use crate::lib::transceiver::Transceiver;
+use crate::{CrashStatus, should_crash};
+
+/// Fault injection state of a node, driven by control messages and kept across crashes
+#[derive(Debug)]
+pub struct NodeControl {
+    paused: bool,
+    random_crashes: bool,
+    pending_crash: Option<CrashStatus>,
+    /// The crash probability is per tick, however often the node is stepped
+    crash_draws: Heartbeat,
+    /// Draws the random crashes, seeded by nodes started with a seed
+    rng: StdRng,
+}
+
+impl NodeControl {
+    pub fn new(random_crashes: bool) -> Self {
+        Self {
+            paused: false,
+            random_crashes,
+            pending_crash: None,
+            crash_draws: Heartbeat::default(),
+            rng: StdRng::from_rng(&mut rand::rng()),
+        }
+    }
+
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn handle_message(&mut self, message: ControlMessage, transceiver: &mut Transceiver) {
+        log::warn!("Got control message {:?}", message);
+        match message {
+            ControlMessage::Crash { duration } => {
+                self.pending_crash = Some(CrashStatus::Crash(duration));
+            }
+            ControlMessage::CrashPermanently => {
+                self.pending_crash = Some(CrashStatus::PermanentCrash);
+            }
+            ControlMessage::Pause => self.paused = true,
+            ControlMessage::Resume => self.paused = false,
+            ControlMessage::DropMessages { percentage } => {
+                transceiver.set_drop_percentage(percentage);
+            }
+            ControlMessage::RandomCrashes(enabled) => self.random_crashes = enabled,
+            ControlMessage::Partition { name, blocked } => transceiver.partition(name, blocked),
+            ControlMessage::Heal { name } => transceiver.heal(name),
+        }
+    }
+
+    /// Requested crashes take precedence over the random crash generator
+    pub fn should_crash(&mut self) -> CrashStatus {
+        match self.pending_crash.take() {
+            Some(crash_status) => crash_status,
+            None if self.random_crashes && self.crash_draws.beat() => should_crash(&mut self.rng),
+            None => CrashStatus::Continue,
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+
+use crate::lib::config::Config;
+use crate::lib::fork::{Fork, ForkRef};
+use crate::lib::node::Node;
+use crate::lib::thinker::{Thinker, ThinkerRef};
+use crate::lib::topology::Topology;
+use crate::lib::utils::Id;
+use crate::lib::visualizer::VisualizerRef;
+
+/// Addresses of every node of a deployment, in ring order
+#[derive(Debug, Clone, Default)]
+pub struct Deployment {
+    pub thinkers: Vec<SocketAddr>,
+    pub forks: Vec<SocketAddr>,
+    pub visualizers: Vec<SocketAddr>,
+}
+
+/// Parses `host:port` or a port range on one host like `192.168.51.10:4000-4004`
+pub fn parse_addresses(value: &str) -> Result<Vec<SocketAddr>, String> {
+    let value = value.trim();
+    let Some((host, ports)) = value.rsplit_once(':') else {
+        return Err(format!("Address {value} is missing a port"));
+    };
+    let port = |port: &str| {
+        port.parse::<u16>()
+            .map_err(|_| format!("Invalid port {port} in {value}"))
+    };
+    let (first, last) = match ports.split_once('-') {
+        Some((first, last)) => (port(first)?, port(last)?),
+        None => (port(ports)?, port(ports)?),
+    };
+    if first > last {
+        return Err(format!("Port range {ports} in {value} is empty"));
+    }
+    (first..=last)
+        .map(|port| {
+            format!("{host}:{port}")
+                .parse()
+                .map_err(|_| format!("Invalid address {host}:{port}"))
+        })
+        .collect()
+}
+
+impl Deployment {
+    /// One `<role> <addresses>` entry per line, `#` starts a comment, e.g. `fork 10.0.0.2:4100-4103`
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut deployment = Self::default();
+        for line in source.lines().map(|line| line.split('#').next().unwrap()) {
+            let mut words = line.split_whitespace();
+            let Some(role) = words.next() else {
+                continue;
+            };
+            let addresses = match role {
+                "thinker" => &mut deployment.thinkers,
+                "fork" => &mut deployment.forks,
+                "visualizer" => &mut deployment.visualizers,
+                _ => return Err(format!("Unknown role {role} in {line:?}")),
+            };
+            for word in words {
+                addresses.extend(parse_addresses(word)?);
+            }
+        }
+        Ok(deployment)
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+        Self::parse(&source)
+    }
+
+    /// Wires the nodes into a ring with fresh ids, `fork_replicas` consecutive forks form one fork
+    pub fn topology(
+        &self,
+        tokens: usize,
+        next_thinkers_amount: usize,
+        fork_replicas: usize,
+    ) -> Result<Topology, String> {
+        if self.thinkers.is_empty() {
+            return Err("A deployment needs at least one thinker".to_string());
+        }
+        if self.forks.len() != self.thinkers.len() * fork_replicas {
+            return Err(format!(
+                "{} thinkers with {fork_replicas} replicas per fork need {} fork addresses, got {}",
+                self.thinkers.len(),
+                self.thinkers.len() * fork_replicas,
+                self.forks.len()
+            ));
+        }
+        let thinkers = self
+            .thinkers
+            .iter()
+            .map(|address| ThinkerRef {
+                address: *address,
+                id: Id::random(),
+            })
+            .collect();
+        let forks = self
+            .forks
+            .iter()
+            .map(|address| ForkRef {
+                address: *address,
+                id: Id::random(),
+                backups: vec![],
+            })
+            .collect();
+        let visualizers = self
+            .visualizers
+            .iter()
+            .map(|address| VisualizerRef {
+                address: *address,
+                kinds: vec![],
+            })
+            .collect();
+        Ok(Topology::ring(
+            thinkers,
+            forks,
+            tokens,
+            visualizers,
+            next_thinkers_amount,
+            fork_replicas,
+        ))
+    }
+}
+
+/// Writes the config of every node or only of those on `host`, the nodes are then started with
+/// their `config` subcommand. Returns how many thinker and fork configs were written.
+pub fn write_configs(topology: &Topology, dir: &Path, host: Option<IpAddr>) -> (usize, usize) {
+    let on_host = |address: &SocketAddr| host.is_none_or(|host| address.ip() == host);
+    let mut written = (0, 0);
+    for (index, thinker) in topology.thinkers.iter().enumerate() {
+        if !on_host(&thinker.address) {
+            continue;
+        }
+        let config = Thinker::config(
+            thinker.id.clone(),
+            thinker.address,
+            &topology.thinker_init(index),
+        );
+        config.write(&dir.join(format!("{}.conf", Thinker::file_name(&config))));
+        written.0 += 1;
+    }
+    for (address, init) in topology.fork_inits() {
+        if !on_host(&address) {
+            continue;
+        }
+        let config = Fork::config(init.id.clone(), address, &init);
+        config.write(&dir.join(format!("{}.conf", Fork::file_name(&config))));
+        written.1 += 1;
+    }
+    written
+}
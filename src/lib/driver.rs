@@ -0,0 +1,178 @@
+//! Async driver to embed nodes in a tokio runtime, enabled with the `tokio` feature
+//!
+//! Nodes are built as usual, with a transceiver from `AsyncSocket::transceiver`, and then run as
+//! futures that wake on socket readiness or the next deadline of the node.
+
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+use crate::lib::events::{EventLog, EventRecord, EventSink};
+use crate::lib::fork::Fork;
+use crate::lib::node::Node;
+use crate::lib::thinker::Thinker;
+use crate::lib::transceiver::Transceiver;
+use crate::lib::transport::Transport;
+use crate::lib::visualizer::{Visualizer, VisualizerEvent};
+use crate::{CrashStatus, NETWORK_BUFFER_SIZE, TICK_INTERVAL};
+
+#[derive(Debug)]
+struct TokioUdpTransport {
+    socket: Arc<UdpSocket>,
+    /// Sends right away, tokio refuses to send until its reactor polled the socket once
+    sender: std::net::UdpSocket,
+}
+
+impl Transport for TokioUdpTransport {
+    fn send_to(&self, bytes: &[u8], to: &SocketAddr) {
+        match self.sender.send_to(bytes, to) {
+            Ok(_) => (),
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock) => {
+                log::warn!("Send buffer is full, dropping message to {to}");
+            }
+            Err(e) => panic!("{:?}", e),
+        }
+    }
+
+    fn recv_from(&self, buffer: &mut [u8]) -> Option<(usize, SocketAddr)> {
+        match self.socket.try_recv_from(buffer) {
+            Ok(bytes) => Some(bytes),
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock) => None,
+            Err(e) => panic!("{:?}", e),
+        }
+    }
+
+    /// The driver awaits readiness instead, blocking here would stall the runtime
+    fn wait(&self, _timeout: Duration) {}
+
+    fn local_address(&self) -> SocketAddr {
+        self.socket.local_addr().unwrap()
+    }
+
+    /// Keeps the socket the driver waits on, only what was not read yet is dropped
+    fn reset(self: Box<Self>) -> Box<dyn Transport> {
+        let mut buffer = [0; NETWORK_BUFFER_SIZE];
+        while self.recv_from(&mut buffer).is_some() {}
+        self
+    }
+}
+
+/// Async UDP socket shared by a node's transceiver and its driver
+#[derive(Debug)]
+pub struct AsyncSocket {
+    socket: Arc<UdpSocket>,
+    sender: std::net::UdpSocket,
+}
+
+impl AsyncSocket {
+    pub async fn bind(address: SocketAddr) -> io::Result<Self> {
+        let sender = UdpSocket::bind(address).await?.into_std()?;
+        Ok(Self {
+            socket: Arc::new(UdpSocket::from_std(sender.try_clone()?)?),
+            sender,
+        })
+    }
+
+    pub fn local_address(&self) -> SocketAddr {
+        self.socket.local_addr().unwrap()
+    }
+
+    /// Transceiver to build the node with
+    pub fn transceiver(&self) -> Transceiver {
+        Transceiver::with_transport(Box::new(TokioUdpTransport {
+            socket: self.socket.clone(),
+            sender: self
+                .sender
+                .try_clone()
+                .unwrap_or_else(|e| panic!("Could not clone socket: {e}")),
+        }))
+    }
+
+    async fn wait(&self, deadline: Instant) {
+        tokio::select! {
+            _ = self.socket.readable() => (),
+            _ = tokio::time::sleep_until(deadline.into()) => (),
+        }
+    }
+}
+
+/// Same loop as `runner::run_node`, with the crashes simulated by sleeping
+async fn run<N: Node + Send>(mut node: N, socket: AsyncSocket) {
+    let mut buffer = [0; NETWORK_BUFFER_SIZE];
+    loop {
+        node.print_started();
+        loop {
+            node.tick(&mut buffer);
+            node.update_visualizer();
+            // Includes when the next message held back by the fault layer is due
+            socket.wait(node.next_deadline()).await;
+            match node.should_crash() {
+                CrashStatus::Continue => (),
+                CrashStatus::Crash(crash_duration) => {
+                    log::info!("{} crashed. Restarting in {:?}", node.id(), crash_duration);
+                    tokio::time::sleep(crash_duration).await;
+                    node = node.reset();
+                    break;
+                }
+                CrashStatus::PermanentCrash => {
+                    log::error!("{} permanently crashed", node.id());
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Runs until the thinker crashes permanently, `socket` has to be the one of its transceiver
+pub fn run_thinker(thinker: Thinker, socket: AsyncSocket) -> impl Future<Output = ()> + Send {
+    run(thinker, socket)
+}
+
+/// Runs until the fork crashes permanently, `socket` has to be the one of its transceiver
+pub fn run_fork(fork: Fork, socket: AsyncSocket) -> impl Future<Output = ()> + Send {
+    run(fork, socket)
+}
+
+async fn visualize(mut visualizer: Visualizer, socket: AsyncSocket) {
+    let mut buffer = [0; NETWORK_BUFFER_SIZE];
+    loop {
+        visualizer.receive(&mut buffer);
+        // Subscriptions are renewed while receiving, messages held back by the fault layer are
+        // due in between
+        socket.wait(visualizer.next_deadline(TICK_INTERVAL)).await;
+    }
+}
+
+/// Handles updates as they arrive, watch it through its dashboard or `visualizer_event_stream`
+pub fn run_visualizer(
+    visualizer: Visualizer,
+    socket: AsyncSocket,
+) -> impl Future<Output = ()> + Send {
+    visualize(visualizer, socket)
+}
+
+/// Every event logged from now on, the log has to be added to the node afterwards
+pub fn event_stream(event_log: &mut EventLog) -> UnboundedReceiverStream<EventRecord> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    event_log.add_sink(EventSink::new(move |record: &EventRecord| {
+        let _ = sender.send(record.clone());
+    }));
+    UnboundedReceiverStream::new(receiver)
+}
+
+/// Every change the visualizer notices from now on
+pub fn visualizer_event_stream(
+    visualizer: &mut Visualizer,
+) -> UnboundedReceiverStream<VisualizerEvent> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    visualizer.add_event_sink(EventSink::new(move |event: &VisualizerEvent| {
+        let _ = sender.send(event.clone());
+    }));
+    UnboundedReceiverStream::new(receiver)
+}
@@ -0,0 +1,357 @@
+use std::fs::{File, OpenOptions};
+use std::io::{LineWriter, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::lib::clock::{SharedClock, VectorClock};
+use crate::lib::fork::Fork;
+use crate::lib::messages::thinker_messages::Token;
+use crate::lib::thinker::Thinker;
+use crate::lib::utils::Id;
+
+/// State transitions of thinkers and forks
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    ThinkerStarted {
+        address: SocketAddr,
+    },
+    GotHungry,
+    GotToken {
+        token: Id<Token>,
+        version: u32,
+    },
+    ForkTaken {
+        fork: Id<Fork>,
+    },
+    /// The primary of a taken fork no longer confirms the grant, the thinker waits for it again
+    ForkUnconfirmed {
+        fork: Id<Fork>,
+    },
+    StartedEating {
+        token: Id<Token>,
+    },
+    FinishedEating {
+        eating_time_ms: u64,
+    },
+    /// Forks stopped answering, the token is passed on and the thinker is hungry again
+    ForksTimedOut {
+        while_eating: bool,
+    },
+    /// The held token was superseded by a newer version
+    TokenInvalidated {
+        token: Id<Token>,
+    },
+    OutdatedTokenDropped {
+        token: Id<Token>,
+        version: u32,
+    },
+    TokenTimedOut {
+        token: Id<Token>,
+        propose_version: u32,
+    },
+    OutdatedProposal {
+        token: Id<Token>,
+        propose_version: u32,
+    },
+    ProposalSteppedDown {
+        token: Id<Token>,
+        issuer: Id<Thinker>,
+    },
+    ProposalDropped {
+        token: Id<Token>,
+        issuer: Id<Thinker>,
+    },
+    OwnProposalOutdated {
+        token: Id<Token>,
+        propose_version: u32,
+    },
+    UnknownTokenProposal {
+        token: Id<Token>,
+    },
+    TokenGenerated {
+        token: Id<Token>,
+    },
+    ForkStarted {
+        address: SocketAddr,
+        rank: usize,
+        replicas: usize,
+    },
+    ThinkerQueued {
+        thinker: Id<Thinker>,
+        position: usize,
+    },
+    /// A queued thinker gave up or shut down
+    ThinkerDequeued {
+        thinker: Id<Thinker>,
+    },
+    ForkGranted {
+        thinker: Id<Thinker>,
+    },
+    ForkReleased {
+        thinker: Id<Thinker>,
+    },
+    HolderTimedOut {
+        thinker: Id<Thinker>,
+    },
+    BecamePrimary {
+        epoch: u32,
+    },
+    SteppedDown {
+        epoch: u32,
+    },
+    ElectionStarted {
+        epoch: u32,
+    },
+    ElectionTimedOut {
+        epoch: u32,
+    },
+    /// Got SIGINT or SIGTERM, what the node holds is handed on before it exits
+    ShuttingDown,
+    ThinkerLeft {
+        thinker: Id<Thinker>,
+    },
+    /// A fork shut down, the token is passed on and the thinker is hungry again
+    ForkLeft {
+        fork: Id<Fork>,
+    },
+    /// Step of a chaos scenario, logged by the runner and not by a node
+    FaultInjected {
+        action: String,
+    },
+}
+
+impl Event {
+    pub fn level(&self) -> log::Level {
+        match self {
+            Event::ForksTimedOut { .. }
+            | Event::ForkUnconfirmed { .. }
+            | Event::TokenInvalidated { .. }
+            | Event::OutdatedTokenDropped { .. }
+            | Event::OutdatedProposal { .. }
+            | Event::OwnProposalOutdated { .. }
+            | Event::UnknownTokenProposal { .. }
+            | Event::HolderTimedOut { .. }
+            | Event::ElectionStarted { .. }
+            | Event::ElectionTimedOut { .. }
+            | Event::ForkLeft { .. }
+            | Event::FaultInjected { .. } => log::Level::Warn,
+            _ => log::Level::Info,
+        }
+    }
+}
+
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Event::ThinkerStarted { address } => write!(f, "Started Thinker {address}"),
+            Event::GotHungry => write!(f, "Got hungry"),
+            Event::GotToken { token, version } => {
+                write!(f, "Got token {token} v{version}, requesting forks")
+            }
+            Event::ForkTaken { fork } => write!(f, "Taken fork {fork}"),
+            Event::ForkUnconfirmed { fork } => {
+                write!(f, "Fork {fork} no longer confirmed, waiting for it again")
+            }
+            Event::StartedEating { token } => write!(f, "Start eating with token {token}"),
+            Event::FinishedEating { eating_time_ms } => {
+                write!(
+                    f,
+                    "Start Thinking after eating {eating_time_ms}ms, release forks"
+                )
+            }
+            Event::ForksTimedOut { while_eating } => write!(
+                f,
+                "Forks timed out while {}, passing token",
+                match while_eating {
+                    true => "eating",
+                    false => "waiting",
+                }
+            ),
+            Event::TokenInvalidated { token } => {
+                write!(f, "Token {token} is outdated, waiting for a new one")
+            }
+            Event::OutdatedTokenDropped { token, version } => {
+                write!(f, "Dropping outdated token {token} v{version}")
+            }
+            Event::TokenTimedOut {
+                token,
+                propose_version,
+            } => write!(
+                f,
+                "Token {token} timed out, switching in proposed state p{propose_version}"
+            ),
+            Event::OutdatedProposal {
+                token,
+                propose_version,
+            } => write!(f, "Outdated proposal {propose_version} for token {token}"),
+            Event::ProposalSteppedDown { token, issuer } => write!(
+                f,
+                "Got proposal from more priority issuer {issuer} for token {token}. Stepping down"
+            ),
+            Event::ProposalDropped { token, issuer } => write!(
+                f,
+                "Got proposal from lower priority issuer {issuer} for token {token}. Dropping proposal"
+            ),
+            Event::OwnProposalOutdated {
+                token,
+                propose_version,
+            } => write!(
+                f,
+                "Dropping own outdated token proposal {propose_version} for token {token}"
+            ),
+            Event::UnknownTokenProposal { token } => {
+                write!(f, "Token proposal for unkown token {token}")
+            }
+            Event::TokenGenerated { token } => write!(f, "Generated new token {token}"),
+            Event::ForkStarted {
+                address,
+                rank,
+                replicas,
+            } => write!(f, "Started fork {address} (replica {rank} of {replicas})"),
+            Event::ThinkerQueued { thinker, position } => {
+                write!(f, "Queued Thinker {thinker} at position {position}")
+            }
+            Event::ThinkerDequeued { thinker } => write!(f, "Thinker {thinker} left the queue"),
+            Event::ForkGranted { thinker } => write!(f, "Fork taken by {thinker}"),
+            Event::ForkReleased { thinker } => write!(f, "Fork released by {thinker}"),
+            Event::HolderTimedOut { thinker } => write!(
+                f,
+                "No keep alive from thinker {thinker}. Releasing fork access"
+            ),
+            Event::BecamePrimary { epoch } => write!(f, "Became primary replica in epoch {epoch}"),
+            Event::SteppedDown { epoch } => {
+                write!(f, "Observed epoch {epoch}, stepping down to backup")
+            }
+            Event::ElectionStarted { epoch } => {
+                write!(f, "Primary timed out, requesting votes for epoch {epoch}")
+            }
+            Event::ElectionTimedOut { epoch } => write!(f, "Election for epoch {epoch} timed out"),
+            Event::ShuttingDown => write!(f, "Shutting down"),
+            Event::ThinkerLeft { thinker } => write!(f, "Thinker {thinker} left"),
+            Event::ForkLeft { fork } => write!(f, "Fork {fork} left, passing token"),
+            Event::FaultInjected { action } => write!(f, "Injected {action}"),
+        }
+    }
+}
+
+/// One line of an event log
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EventRecord {
+    pub node: Uuid,
+    pub wall_time_ms: u64,
+    pub logical_time: u64,
+    /// Only kept by nodes started with a vector clock
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock: Option<VectorClock>,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// Reads an NDJSON event log, lines that do not parse are skipped with a warning
+pub fn read_event_log(path: &Path) -> Result<Vec<EventRecord>, String> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+    Ok(source
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(index, line)| match serde_json::from_str(line) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                log::warn!("Skipping line {} of {}: {e}", index + 1, path.display());
+                None
+            }
+        })
+        .collect())
+}
+
+/// Gets every event, e.g. to forward it into a channel
+pub struct EventSink<T>(Box<dyn FnMut(&T) + Send>);
+
+impl<T> EventSink<T> {
+    pub fn new(sink: impl FnMut(&T) + Send + 'static) -> Self {
+        Self(Box::new(sink))
+    }
+
+    pub fn send(&mut self, event: &T) {
+        (self.0)(event)
+    }
+}
+
+impl<T> std::fmt::Debug for EventSink<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EventSink")
+    }
+}
+
+/// Logs every event as text and optionally appends it as JSON line to a file
+///
+/// `logical_time` counts the events of this node and is kept across simulated crashes
+#[derive(Debug, Default)]
+pub struct EventLog {
+    node: Uuid,
+    writer: Option<LineWriter<File>>,
+    logical_time: u64,
+    clock: Option<SharedClock>,
+    sinks: Vec<EventSink<EventRecord>>,
+}
+
+impl EventLog {
+    /// Appends to `path` so the history survives restarts of the process
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        Ok(Self {
+            writer: Some(LineWriter::new(
+                OpenOptions::new().create(true).append(true).open(path)?,
+            )),
+            ..Self::default()
+        })
+    }
+
+    pub fn set_node(&mut self, node: Uuid) {
+        self.node = node;
+    }
+
+    /// Events count as local steps of the clock and are stamped with it
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = Some(clock);
+    }
+
+    pub fn add_sink(&mut self, sink: EventSink<EventRecord>) {
+        self.sinks.push(sink);
+    }
+
+    pub fn emit(&mut self, event: Event) {
+        self.logical_time += 1;
+        log::log!(event.level(), "{event}");
+        let clock = self.clock.as_ref().map(SharedClock::tick);
+        if self.writer.is_none() && self.sinks.is_empty() {
+            return;
+        }
+        let record = EventRecord {
+            node: self.node,
+            wall_time_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            logical_time: self.logical_time,
+            clock,
+            event,
+        };
+        for sink in &mut self.sinks {
+            sink.send(&record);
+        }
+        let Some(writer) = &mut self.writer else {
+            return;
+        };
+        let mut line = serde_json::to_string(&record).unwrap();
+        line.push('\n');
+        if let Err(e) = writer.write_all(line.as_bytes()) {
+            log::error!("Could not write event log: {e}");
+        }
+    }
+}
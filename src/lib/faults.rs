@@ -0,0 +1,377 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+use crate::KEEP_MESSAGE_PERCENTAGE;
+use crate::lib::utils::parse_duration;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Latency {
+    Fixed(Duration),
+    Uniform { min: Duration, max: Duration },
+    Exponential { mean: Duration },
+}
+
+impl Latency {
+    fn sample(&self, rng: &mut impl Rng) -> Duration {
+        match self {
+            Latency::Fixed(latency) => *latency,
+            Latency::Uniform { min, max } => rng.random_range(*min..=*max),
+            Latency::Exponential { mean } => {
+                let uniform: f64 = rng.random_range(f64::EPSILON..1.0);
+                mean.mul_f64(-uniform.ln())
+            }
+        }
+    }
+
+    /// Parses `20ms`, `10ms..40ms` or `exp 30ms`
+    fn parse(words: &[&str]) -> Result<Self, String> {
+        match words {
+            ["exp", mean] => Ok(Latency::Exponential {
+                mean: parse_duration(mean)?,
+            }),
+            [range] => match range.split_once("..") {
+                Some((min, max)) => {
+                    let (min, max) = (parse_duration(min)?, parse_duration(max)?);
+                    if min > max {
+                        return Err(format!("Latency range {range} is empty"));
+                    }
+                    Ok(Latency::Uniform { min, max })
+                }
+                None => Ok(Latency::Fixed(parse_duration(range)?)),
+            },
+            _ => Err(format!("Invalid latency {}", words.join(" "))),
+        }
+    }
+}
+
+/// Addresses are compared by port only if one of them is unspecified, configs store bound addresses
+fn same_endpoint(a: &SocketAddr, b: &SocketAddr) -> bool {
+    a == b || (a.port() == b.port() && (a.ip().is_unspecified() || b.ip().is_unspecified()))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkLatency {
+    pub between: [SocketAddr; 2],
+    pub latency: Latency,
+}
+
+impl LinkLatency {
+    fn matches(&self, a: &SocketAddr, b: &SocketAddr) -> bool {
+        (same_endpoint(&self.between[0], a) && same_endpoint(&self.between[1], b))
+            || (same_endpoint(&self.between[0], b) && same_endpoint(&self.between[1], a))
+    }
+}
+
+/// Cuts every link between the two address sets
+#[derive(Debug, Clone, PartialEq)]
+pub struct Partition {
+    pub name: String,
+    pub sides: [Vec<SocketAddr>; 2],
+}
+
+impl Partition {
+    fn cuts(&self, a: &SocketAddr, b: &SocketAddr) -> bool {
+        let contains = |side: &Vec<SocketAddr>, address: &SocketAddr| {
+            side.iter().any(|el| same_endpoint(el, address))
+        };
+        (contains(&self.sides[0], a) && contains(&self.sides[1], b))
+            || (contains(&self.sides[1], a) && contains(&self.sides[0], b))
+    }
+}
+
+/// Network faults applied by a `Transceiver`, the same for UDP and in-process transports
+///
+/// ```text
+/// drop 0.05
+/// duplicate 0.01
+/// reorder 50ms
+/// latency 10ms..40ms
+/// link 127.0.0.1:4000 127.0.0.1:4001 latency exp 120ms
+/// partition west 127.0.0.1:4000,127.0.0.1:4001 | 127.0.0.1:4002
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultConfig {
+    pub drop_percentage: f64,
+    pub duplicate_percentage: f64,
+    /// Upper bound of an additional random delay, messages inside the window may overtake each other
+    pub reorder_window: Duration,
+    pub latency: Latency,
+    pub links: Vec<LinkLatency>,
+    pub partitions: Vec<Partition>,
+}
+
+impl Default for FaultConfig {
+    fn default() -> Self {
+        Self {
+            drop_percentage: 1.0 - KEEP_MESSAGE_PERCENTAGE,
+            duplicate_percentage: 0.0,
+            reorder_window: Duration::ZERO,
+            latency: Latency::Fixed(Duration::ZERO),
+            links: vec![],
+            partitions: vec![],
+        }
+    }
+}
+
+fn parse_addresses(addresses: &str) -> Result<Vec<SocketAddr>, String> {
+    addresses
+        .split(',')
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .map(|address| {
+            address
+                .parse()
+                .map_err(|_| format!("Invalid address {address}"))
+        })
+        .collect()
+}
+
+/// Parses a fraction of messages between 0 and 1
+pub fn parse_percentage(value: &str) -> Result<f64, String> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|percentage| (0.0..=1.0).contains(percentage))
+        .ok_or_else(|| format!("Invalid percentage {value}, expected 0 to 1"))
+}
+
+impl FaultConfig {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+        for line in source.lines().map(|line| line.split('#').next().unwrap()) {
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            match words.as_slice() {
+                [] => (),
+                ["drop", value] => config.drop_percentage = parse_percentage(value)?,
+                ["duplicate", value] => config.duplicate_percentage = parse_percentage(value)?,
+                ["reorder", window] => config.reorder_window = parse_duration(window)?,
+                ["latency", latency @ ..] => config.latency = Latency::parse(latency)?,
+                ["link", a, b, "latency", latency @ ..] => config.links.push(LinkLatency {
+                    between: [
+                        a.parse().map_err(|_| format!("Invalid address {a}"))?,
+                        b.parse().map_err(|_| format!("Invalid address {b}"))?,
+                    ],
+                    latency: Latency::parse(latency)?,
+                }),
+                ["partition", name, ..] => {
+                    // The name may occur inside the keyword, so skip both words by their length
+                    let sides = line.trim_start()["partition".len()..].trim_start()[name.len()..]
+                        .split('|')
+                        .map(parse_addresses)
+                        .collect::<Result<Vec<_>, _>>()?;
+                    config.partitions.push(Partition {
+                        name: name.to_string(),
+                        sides: sides
+                            .try_into()
+                            .map_err(|_| format!("Partition {name} needs exactly two sides"))?,
+                    });
+                }
+                _ => Err(format!("Invalid fault config line {line}"))?,
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+        Self::parse(&source)
+    }
+
+    fn latency(&self, a: &SocketAddr, b: &SocketAddr) -> &Latency {
+        self.links
+            .iter()
+            .find(|link| link.matches(a, b))
+            .map(|link| &link.latency)
+            .unwrap_or(&self.latency)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct DelayedMessage {
+    deliver_at: Instant,
+    /// Keeps messages with the same delivery time in arrival order
+    sequence: u64,
+    bytes: Vec<u8>,
+    from: SocketAddr,
+}
+
+/// Drops outgoing messages and delays, duplicates and reorders incoming ones
+#[derive(Debug)]
+pub struct FaultLayer {
+    pub config: FaultConfig,
+    delayed: BinaryHeap<Reverse<DelayedMessage>>,
+    sequence: u64,
+}
+
+impl FaultLayer {
+    pub fn new(config: FaultConfig) -> Self {
+        Self {
+            config,
+            delayed: BinaryHeap::new(),
+            sequence: 0,
+        }
+    }
+
+    pub fn reset(self) -> Self {
+        Self::new(self.config)
+    }
+
+    pub fn is_partitioned(&self, local: &SocketAddr, remote: &SocketAddr) -> bool {
+        self.config
+            .partitions
+            .iter()
+            .any(|partition| partition.cuts(local, remote))
+    }
+
+    /// Simulated message loss, partitions are checked separately
+    pub fn is_lost(&self) -> bool {
+        rand::rng().random_bool(self.config.drop_percentage.clamp(0.0, 1.0))
+    }
+
+    /// Queues a received datagram for delayed delivery, false if a partition dropped it
+    pub fn incoming(&mut self, bytes: &[u8], local: &SocketAddr, from: SocketAddr) -> bool {
+        if self.is_partitioned(local, &from) {
+            return false;
+        }
+        let mut rng = rand::rng();
+        let copies = match rng.random_bool(self.config.duplicate_percentage.clamp(0.0, 1.0)) {
+            true => 2,
+            false => 1,
+        };
+        for _ in 0..copies {
+            let mut delay = self.config.latency(local, &from).sample(&mut rng);
+            if !self.config.reorder_window.is_zero() {
+                delay += rng.random_range(Duration::ZERO..=self.config.reorder_window);
+            }
+            self.sequence += 1;
+            self.delayed.push(Reverse(DelayedMessage {
+                deliver_at: Instant::now() + delay,
+                sequence: self.sequence,
+                bytes: bytes.to_vec(),
+                from,
+            }));
+        }
+        true
+    }
+
+    pub fn next_delivery(&self) -> Option<Instant> {
+        self.delayed
+            .peek()
+            .map(|Reverse(message)| message.deliver_at)
+    }
+
+    /// Next message whose delay has passed
+    pub fn deliver(&mut self) -> Option<(Vec<u8>, SocketAddr)> {
+        match self.next_delivery() {
+            Some(deliver_at) if deliver_at <= Instant::now() => {
+                let Reverse(message) = self.delayed.pop().unwrap();
+                Some((message.bytes, message.from))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn partition(&mut self, partition: Partition) {
+        self.config
+            .partitions
+            .retain(|existing| existing.name.ne(&partition.name));
+        self.config.partitions.push(partition);
+    }
+
+    pub fn heal(&mut self, name: Option<String>) {
+        match name {
+            Some(name) => self
+                .config
+                .partitions
+                .retain(|existing| existing.name.ne(&name)),
+            None => self.config.partitions.clear(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn parses_every_line_kind() {
+        let config = FaultConfig::parse(
+            "drop 0.05 # lossy\n\
+             duplicate 0.01\n\
+             reorder 50ms\n\
+             latency 10ms..40ms\n\
+             link 127.0.0.1:4000 127.0.0.1:4001 latency exp 120ms\n\
+             partition west 127.0.0.1:4000,127.0.0.1:4001 | 127.0.0.1:4002\n",
+        )
+        .unwrap();
+        assert_eq!(config.drop_percentage, 0.05);
+        assert_eq!(config.duplicate_percentage, 0.01);
+        assert_eq!(config.reorder_window, Duration::from_millis(50));
+        assert_eq!(
+            config.latency,
+            Latency::Uniform {
+                min: Duration::from_millis(10),
+                max: Duration::from_millis(40)
+            }
+        );
+        assert_eq!(
+            config.links,
+            vec![LinkLatency {
+                between: [address(4000), address(4001)],
+                latency: Latency::Exponential {
+                    mean: Duration::from_millis(120)
+                },
+            }]
+        );
+        assert_eq!(
+            config.partitions,
+            vec![Partition {
+                name: "west".to_string(),
+                sides: [vec![address(4000), address(4001)], vec![address(4002)]],
+            }]
+        );
+    }
+
+    #[test]
+    fn partition_names_may_occur_in_the_keyword() {
+        for name in ["p", "a", "t", "on", "part", "partition"] {
+            let config = FaultConfig::parse(&format!(
+                "  partition  {name} 127.0.0.1:4000 | 127.0.0.1:4001"
+            ))
+            .unwrap();
+            assert_eq!(config.partitions[0].name, name);
+            assert_eq!(
+                config.partitions[0].sides,
+                [vec![address(4000)], vec![address(4001)]]
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        for line in [
+            "latency 40ms..10ms",
+            "reorder -5ms",
+            "drop lots",
+            "drop NaN",
+            "drop 1.5",
+            "duplicate NaN",
+            "duplicate inf",
+            "partition west 127.0.0.1:4000",
+            "partition west 127.0.0.1:4000 | nowhere",
+            "jitter 5ms",
+        ] {
+            assert!(FaultConfig::parse(line).is_err(), "{line} should not parse");
+        }
+    }
+}
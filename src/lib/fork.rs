@@ -1,22 +1,39 @@
-use std::collections::VecDeque;
+use std::collections::{BTreeSet, VecDeque};
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::time::Instant;
 
 use rkyv::{Archive, Deserialize, Serialize};
+use uuid::Uuid;
 
-use crate::KEEP_ALIVE_TIMEOUT;
+use crate::lib::config::{Config, ForkConfig};
+use crate::lib::control::NodeControl;
+use crate::lib::events::{Event, EventLog};
+use crate::lib::messages::fork_messages::InitForkParams;
 use crate::lib::messages::thinker_messages::ForkState;
 use crate::lib::messages::visualizer_messages::VisualizerForkState;
-use crate::lib::messages::{ForkMessages, ThinkerMessage, VisualizerMessages};
-use crate::lib::thinker::ThinkerRef;
+use crate::lib::messages::{ForkMessages, InitMessages, ThinkerMessage, VisualizerMessages};
+use crate::lib::metrics::{FORKS_GRANTED, FORKS_TIMED_OUT};
+use crate::lib::node::{Node, NodeSetup};
+use crate::lib::runner::{Heartbeat, LoopMode};
+use crate::lib::thinker::{Thinker, ThinkerRef};
 use crate::lib::transceiver::Transceiver;
 use crate::lib::utils::{EntityType, Id};
-use crate::lib::visualizer::VisualizerRef;
+use crate::lib::visualizer::{self, Subscribers, VisualizerRef};
+use crate::{CrashStatus, FORK_FAILOVER_TIMEOUT, FORK_LEASE, KEEP_ALIVE_TIMEOUT, TICK_INTERVAL};
 
-#[derive(Archive, Serialize, Deserialize, Clone, Debug)]
+#[derive(Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub struct ForkRef {
     pub address: SocketAddr,
     pub id: Id<Fork>,
+    pub backups: Vec<SocketAddr>,
+}
+
+impl ForkRef {
+    /// Primary address followed by all backups
+    pub fn replicas(&self) -> impl Iterator<Item = &SocketAddr> {
+        std::iter::once(&self.address).chain(&self.backups)
+    }
 }
 
 #[derive(Debug)]
@@ -45,11 +62,63 @@ struct QueuedThinker {
     thinker: ThinkerRef,
 }
 
+#[derive(
+    Archive, Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord,
+)]
+pub struct ReplicaVersion {
+    pub epoch: u32,
+    pub sequence: u32,
+}
+
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplicatedForkState {
+    pub version: ReplicaVersion,
+    pub holder: Option<ThinkerRef>,
+    pub queue: Vec<ThinkerRef>,
+}
+
+/// Replication state that has to be durable, otherwise a restarted replica could vote twice
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ForkCheckpoint {
+    pub epoch: u32,
+    pub voted_epoch: u32,
+    pub state: ReplicatedForkState,
+}
+
+#[derive(Debug)]
+enum ReplicaRole {
+    Primary {
+        acknowledged: Vec<ReplicaVersion>,
+        /// Send time of the latest round each replica acknowledged in this epoch
+        acknowledged_at: Vec<Option<Instant>>,
+        round: u32,
+        /// Rounds still young enough to extend the lease
+        rounds: VecDeque<(u32, Instant)>,
+        elected_at: Instant,
+    },
+    Backup {
+        primary_last_seen_at: Instant,
+    },
+    Candidate {
+        /// Ranks of the replicas that voted, duplicated votes count once
+        votes: BTreeSet<usize>,
+        started_at: Instant,
+    },
+}
+
 pub struct ForkInitParams {
     pub id: Id<Fork>,
     pub transceiver: Transceiver,
-    pub visualizer: Option<VisualizerRef>,
+    pub visualizers: Vec<VisualizerRef>,
     pub unhandled_messages: Vec<(ForkMessages, SocketAddr)>,
+    pub replicas: Vec<SocketAddr>,
+    pub rank: usize,
+    pub checkpoint: Option<ForkCheckpoint>,
+    pub checkpoint_file: Option<PathBuf>,
+    pub control: NodeControl,
+    pub event_log: EventLog,
+    /// Seeds the random crashes, kept by the control across crashes
+    pub seed: Option<u64>,
 }
 
 #[derive(Debug)]
@@ -58,18 +127,83 @@ pub struct Fork {
     state: ForkStateInternal,
     queue: VecDeque<QueuedThinker>,
     transceiver: Transceiver,
-    visualizer: Option<VisualizerRef>,
+    visualizers: Vec<VisualizerRef>,
+    subscribers: Subscribers,
+    replicas: Vec<SocketAddr>,
+    rank: usize,
+    role: ReplicaRole,
+    epoch: u32,
+    voted_epoch: u32,
+    /// Last time a replicate was acknowledged, votes are only given once its lease may have run out
+    replicated_at: Instant,
+    version: ReplicaVersion,
+    /// Version that made the current holder the holder, it is only confirmed once a majority has it
+    holder_since: ReplicaVersion,
+    checkpoint_file: Option<PathBuf>,
+    last_checkpoint: Option<ForkCheckpoint>,
+    control: NodeControl,
+    events: EventLog,
+    heartbeat: Heartbeat,
+    /// Whether the last step was a heartbeat, only then the state is replicated
+    beat: bool,
+    /// Last state and queue sent to the visualizers, changes are sent right away
+    visualized: Option<(VisualizerForkState, Vec<Id<Thinker>>)>,
+}
+
+/// Clock entry and event log lane of one replica, all replicas share the id of their fork
+///
+/// The rank goes into the leading bits, which short ids show, rank 0 keeps the fork id.
+pub fn replica_node(fork: &Id<Fork>, rank: usize) -> Uuid {
+    Uuid::from_u128(fork.value.as_u128() ^ ((rank as u128) << 96))
 }
 
 impl Fork {
-    pub fn new(init_params: ForkInitParams) -> Self {
+    pub fn new(mut init_params: ForkInitParams) -> Self {
+        if let Some(seed) = init_params.seed {
+            init_params.control.seed(seed);
+        }
+        let node = replica_node(&init_params.id, init_params.rank);
+        init_params.event_log.set_node(node);
+        if let Some(clock) = init_params.transceiver.clock() {
+            clock.set_node(node);
+            init_params.event_log.set_clock(clock.clone());
+        }
         let mut fork = Self {
             id: init_params.id,
             state: ForkStateInternal::Unused,
             queue: VecDeque::new(),
             transceiver: init_params.transceiver,
-            visualizer: init_params.visualizer,
+            visualizers: init_params.visualizers,
+            subscribers: Subscribers::default(),
+            role: ReplicaRole::Backup {
+                primary_last_seen_at: Instant::now(),
+            },
+            rank: init_params.rank,
+            epoch: 0,
+            voted_epoch: 0,
+            replicated_at: Instant::now(),
+            version: ReplicaVersion::default(),
+            holder_since: ReplicaVersion::default(),
+            checkpoint_file: init_params.checkpoint_file,
+            last_checkpoint: None,
+            replicas: init_params.replicas,
+            control: init_params.control,
+            events: init_params.event_log,
+            heartbeat: Heartbeat::default(),
+            beat: false,
+            visualized: None,
         };
+        if let Some(checkpoint) = init_params.checkpoint {
+            fork.epoch = checkpoint.epoch;
+            fork.voted_epoch = checkpoint.voted_epoch;
+            fork.adopt_state(checkpoint.state.clone());
+            fork.last_checkpoint = Some(checkpoint);
+        }
+        // A restarted replica may lag what the others acknowledged, so only a fresh rank 0 skips
+        // the election
+        if fork.replicas.len() <= 1 || (fork.rank == 0 && fork.last_checkpoint.is_none()) {
+            fork.become_primary();
+        }
         init_params
             .unhandled_messages
             .into_iter()
@@ -79,33 +213,182 @@ impl Fork {
         fork
     }
 
-    pub fn reset(self) -> Self {
-        Self::new(ForkInitParams {
-            id: self.id,
-            transceiver: self.transceiver.reset(),
-            visualizer: self.visualizer,
-            unhandled_messages: vec![],
-        })
+    pub fn is_primary(&self) -> bool {
+        matches!(self.role, ReplicaRole::Primary { .. })
     }
 
-    pub fn print_started(&self) {
-        log::info!(
-            "Started fork {} {}",
-            self.transceiver.local_address(),
-            self.id,
-        )
+    fn majority(&self) -> usize {
+        self.replicas.len() / 2 + 1
     }
 
-    pub fn tick(&mut self, buffer: &mut [u8]) {
-        while let Some((message, entity)) = self.transceiver.receive::<ForkMessages>(buffer) {
-            self.handle_message(message, entity);
+    fn other_replicas(&self) -> impl Iterator<Item = &SocketAddr> {
+        let rank = self.rank;
+        self.replicas
+            .iter()
+            .enumerate()
+            .filter(move |(index, _)| *index != rank)
+            .map(|(_, address)| address)
+    }
+
+    /// Highest version a majority of replicas (including this one) has acknowledged
+    fn committed_version(&self) -> ReplicaVersion {
+        match &self.role {
+            ReplicaRole::Primary { acknowledged, .. } => {
+                let mut versions = acknowledged.clone();
+                versions[self.rank] = self.version;
+                versions.sort_by(|a, b| b.cmp(a));
+                versions[self.majority() - 1]
+            }
+            ReplicaRole::Backup { .. } | ReplicaRole::Candidate { .. } => ReplicaVersion::default(),
+        }
+    }
+
+    fn holder_committed(&self) -> bool {
+        self.committed_version() >= self.holder_since
+    }
+
+    /// Whether a majority acknowledged a round sent within `FORK_LEASE`, a primary cut off from
+    /// its backups loses the lease before they can elect a new one
+    fn has_lease(&self) -> bool {
+        let ReplicaRole::Primary {
+            acknowledged_at, ..
+        } = &self.role
+        else {
+            return false;
+        };
+        let fresh = acknowledged_at
+            .iter()
+            .enumerate()
+            .filter(|(index, at)| {
+                *index == self.rank || at.is_some_and(|at| at.elapsed() < FORK_LEASE)
+            })
+            .count();
+        fresh >= self.majority()
+    }
+
+    /// Only a committed holder confirmed under the lease may eat
+    fn holder_confirmed(&self) -> bool {
+        self.holder_committed() && self.has_lease()
+    }
+
+    /// A new primary waits out the lease of its predecessor before it grants to a new holder
+    fn may_grant(&self) -> bool {
+        match &self.role {
+            ReplicaRole::Primary { elected_at, .. } => {
+                self.replicas.len() <= 1 || elected_at.elapsed() >= FORK_LEASE
+            }
+            ReplicaRole::Backup { .. } | ReplicaRole::Candidate { .. } => false,
+        }
+    }
+
+    fn state_changed(&mut self) {
+        self.version = ReplicaVersion {
+            epoch: self.epoch,
+            sequence: self.version.sequence + 1,
+        };
+    }
+
+    fn replicated_state(&self) -> ReplicatedForkState {
+        ReplicatedForkState {
+            version: self.version,
+            holder: match &self.state {
+                ForkStateInternal::Unused => None,
+                ForkStateInternal::Used { thinker, .. } => Some(thinker.clone()),
+            },
+            queue: self
+                .queue
+                .iter()
+                .map(|queued| queued.thinker.clone())
+                .collect(),
+        }
+    }
+
+    fn adopt_state(&mut self, state: ReplicatedForkState) {
+        self.version = state.version;
+        self.state = match state.holder {
+            Some(thinker) => ForkStateInternal::Used {
+                thinker,
+                last_seen_at: Instant::now(),
+            },
+            None => ForkStateInternal::Unused,
+        };
+        self.queue = state
+            .queue
+            .into_iter()
+            .map(|thinker| QueuedThinker {
+                last_seen_at: Instant::now(),
+                thinker,
+            })
+            .collect();
+    }
+
+    fn become_primary(&mut self) {
+        self.role = ReplicaRole::Primary {
+            acknowledged: vec![ReplicaVersion::default(); self.replicas.len()],
+            acknowledged_at: vec![None; self.replicas.len()],
+            round: 0,
+            rounds: VecDeque::new(),
+            elected_at: Instant::now(),
+        };
+        self.state_changed();
+        // The inherited holder may never have been confirmed, so it has to be committed again
+        self.holder_since = self.version;
+        if let ForkStateInternal::Used { last_seen_at, .. } = &mut self.state {
+            *last_seen_at = Instant::now();
+        }
+        self.queue
+            .iter_mut()
+            .for_each(|queued| queued.last_seen_at = Instant::now());
+        if self.replicas.len() > 1 {
+            self.events.emit(Event::BecamePrimary { epoch: self.epoch });
+        }
+    }
+
+    fn step_down(&mut self, epoch: u32) {
+        if !matches!(self.role, ReplicaRole::Backup { .. }) {
+            self.events.emit(Event::SteppedDown { epoch });
+        }
+        self.epoch = epoch;
+        self.role = ReplicaRole::Backup {
+            primary_last_seen_at: Instant::now(),
+        };
+    }
+
+    pub fn state(&self) -> VisualizerForkState {
+        VisualizerForkState::from(&self.state)
+    }
+
+    pub fn checkpoint(&self) -> Option<ForkCheckpoint> {
+        (self.replicas.len() > 1).then(|| ForkCheckpoint {
+            epoch: self.epoch,
+            voted_epoch: self.voted_epoch,
+            state: self.replicated_state(),
+        })
+    }
+
+    fn save_checkpoint(&mut self) {
+        let Some(checkpoint) = self.checkpoint() else {
+            return;
+        };
+        if self.last_checkpoint.as_ref() != Some(&checkpoint) {
+            if let Some(checkpoint_file) = &self.checkpoint_file {
+                checkpoint.write_atomic(checkpoint_file);
+            }
+            self.last_checkpoint = Some(checkpoint);
         }
-        self.update_state();
     }
 
     pub fn handle_message(&mut self, message: ForkMessages, entity: SocketAddr) {
         match message {
+            ForkMessages::KeepAlive(_) | ForkMessages::Release(_) if !self.is_primary() => {
+                // Thinkers address every replica, only the primary answers
+            }
             ForkMessages::KeepAlive(thinker_id) => {
+                let holder_state = match self.holder_confirmed() {
+                    true => ForkState::Taken,
+                    false => ForkState::Queued,
+                };
+                let mut queued_new_thinker = false;
                 match &mut self.state {
                     ForkStateInternal::Unused => {
                         if let Some(queued) = self
@@ -137,11 +420,11 @@ impl Fork {
                                 &queued_thinker.thinker.address,
                             );
                             self.queue.push_back(queued_thinker);
-                            log::info!(
-                                "Queued Thinker {} at position {}",
-                                &thinker_id,
-                                self.queue.len()
-                            );
+                            queued_new_thinker = true;
+                            self.events.emit(Event::ThinkerQueued {
+                                thinker: thinker_id.clone(),
+                                position: self.queue.len(),
+                            });
                         }
                     }
                     ForkStateInternal::Used {
@@ -153,7 +436,7 @@ impl Fork {
                             self.transceiver.send(
                                 ThinkerMessage::ForkAlive {
                                     id: self.id.clone(),
-                                    state: ForkState::Taken,
+                                    state: holder_state,
                                 },
                                 &thinker.address,
                             );
@@ -186,19 +469,31 @@ impl Fork {
                                 &queued_thinker.thinker.address,
                             );
                             self.queue.push_back(queued_thinker);
-                            log::info!(
-                                "Queued Thinker {} at position {}",
-                                &thinker_id,
-                                self.queue.len()
-                            );
+                            queued_new_thinker = true;
+                            self.events.emit(Event::ThinkerQueued {
+                                thinker: thinker_id.clone(),
+                                position: self.queue.len(),
+                            });
                         }
                     }
                 };
+                if queued_new_thinker {
+                    self.state_changed();
+                }
             }
             ForkMessages::Release(id) => match &self.state {
                 ForkStateInternal::Used { thinker, .. } if thinker.id.eq(&id) => {
-                    log::info!("Fork released by {}", thinker.id);
+                    self.events.emit(Event::ForkReleased {
+                        thinker: thinker.id.clone(),
+                    });
                     self.state = ForkStateInternal::Unused;
+                    self.state_changed();
+                }
+                // Queued thinkers release when they give up or shut down
+                _ if self.queue.iter().any(|queued| queued.thinker.id.eq(&id)) => {
+                    self.queue.retain(|queued| queued.thinker.id.ne(&id));
+                    self.events.emit(Event::ThinkerDequeued { thinker: id });
+                    self.state_changed();
                 }
                 ForkStateInternal::Used { .. } => {
                     log::error!(
@@ -213,22 +508,213 @@ impl Fork {
             ForkMessages::Init(_) => {
                 log::error!("Already initialized but got init message from {entity}");
             }
+            ForkMessages::Control(message) => {
+                self.control.handle_message(message, &mut self.transceiver);
+            }
+            ForkMessages::Subscribe { kinds } => {
+                log::info!("Visualizer {entity} subscribed");
+                self.subscribers.subscribe(
+                    &mut self.visualizers,
+                    VisualizerRef {
+                        address: entity,
+                        kinds,
+                    },
+                );
+            }
+            ForkMessages::Replicate {
+                epoch,
+                round,
+                state,
+            } => {
+                if epoch < self.epoch {
+                    self.transceiver.send(
+                        ForkMessages::ReplicateAck {
+                            epoch: self.epoch,
+                            round,
+                            version: self.version,
+                        },
+                        &entity,
+                    );
+                    return;
+                }
+                if epoch == self.epoch && self.is_primary() {
+                    log::error!("Got replicate from {entity} in own epoch {epoch}");
+                    return;
+                }
+                self.step_down(epoch);
+                if state.version > self.version {
+                    self.adopt_state(state);
+                }
+                self.replicated_at = Instant::now();
+                self.transceiver.send(
+                    ForkMessages::ReplicateAck {
+                        epoch,
+                        round,
+                        version: self.version,
+                    },
+                    &entity,
+                );
+            }
+            ForkMessages::ReplicateAck {
+                epoch,
+                round,
+                version,
+            } => {
+                let holder_confirmed = self.holder_confirmed();
+                if epoch > self.epoch {
+                    self.step_down(epoch);
+                } else if let ReplicaRole::Primary {
+                    acknowledged,
+                    acknowledged_at,
+                    rounds,
+                    ..
+                } = &mut self.role
+                    && epoch == self.epoch
+                    && let Some(index) = self.replicas.iter().position(|el| el.eq(&entity))
+                {
+                    acknowledged[index] = acknowledged[index].max(version);
+                    if let Some((_, sent_at)) = rounds.iter().find(|(sent, _)| *sent == round) {
+                        acknowledged_at[index] = acknowledged_at[index].max(Some(*sent_at));
+                    }
+                }
+                if !holder_confirmed && self.holder_confirmed() {
+                    self.notify_holder();
+                }
+            }
+            ForkMessages::RequestVote { epoch, version } => {
+                // Not the backup timer, a failed election restarts that without hearing from a primary
+                let primary_recently_seen =
+                    self.is_primary() || self.replicated_at.elapsed() < FORK_FAILOVER_TIMEOUT;
+                let has_seen_primary = self.version != ReplicaVersion::default();
+                if !primary_recently_seen
+                    && has_seen_primary
+                    && epoch > self.voted_epoch
+                    && epoch >= self.epoch
+                    && version >= self.version
+                {
+                    self.step_down(epoch);
+                    self.voted_epoch = epoch;
+                    self.transceiver.send(ForkMessages::Vote { epoch }, &entity);
+                }
+            }
+            ForkMessages::Vote { epoch } => {
+                if let ReplicaRole::Candidate { votes, .. } = &mut self.role
+                    && epoch == self.epoch
+                    && let Some(rank) = self.replicas.iter().position(|el| el.eq(&entity))
+                {
+                    votes.insert(rank);
+                    if votes.len() >= self.majority() {
+                        self.become_primary();
+                    }
+                }
+            }
         }
     }
 
+    /// Tells a new holder right away instead of with the answer to its next keep alive
+    fn notify_holder(&self) {
+        if let ForkStateInternal::Used { thinker, .. } = &self.state {
+            self.transceiver.send(
+                ThinkerMessage::ForkAlive {
+                    id: self.id.clone(),
+                    state: ForkState::Taken,
+                },
+                &thinker.address,
+            );
+        }
+    }
+
+    fn update_replication(&mut self) {
+        match &mut self.role {
+            ReplicaRole::Primary { .. } if !self.beat => (),
+            ReplicaRole::Primary { round, rounds, .. } => {
+                *round += 1;
+                let round = *round;
+                rounds.push_back((round, Instant::now()));
+                while rounds
+                    .front()
+                    .is_some_and(|(_, sent_at)| sent_at.elapsed() >= FORK_LEASE)
+                {
+                    rounds.pop_front();
+                }
+                let state = self.replicated_state();
+                self.other_replicas().for_each(|replica| {
+                    self.transceiver.send(
+                        ForkMessages::Replicate {
+                            epoch: self.epoch,
+                            round,
+                            state: state.clone(),
+                        },
+                        replica,
+                    );
+                });
+            }
+            ReplicaRole::Backup {
+                primary_last_seen_at,
+            } => {
+                // Staggered by rank so replicas rarely split the vote
+                let timeout = FORK_FAILOVER_TIMEOUT + TICK_INTERVAL * 2 * self.rank as u32;
+                let has_seen_primary = self.version != ReplicaVersion::default();
+                if has_seen_primary && primary_last_seen_at.elapsed() > timeout {
+                    self.epoch += 1;
+                    self.voted_epoch = self.epoch;
+                    self.role = ReplicaRole::Candidate {
+                        votes: BTreeSet::from([self.rank]),
+                        started_at: Instant::now(),
+                    };
+                    self.events
+                        .emit(Event::ElectionStarted { epoch: self.epoch });
+                    self.other_replicas().for_each(|replica| {
+                        self.transceiver.send(
+                            ForkMessages::RequestVote {
+                                epoch: self.epoch,
+                                version: self.version,
+                            },
+                            replica,
+                        );
+                    });
+                }
+            }
+            ReplicaRole::Candidate { started_at, .. } => {
+                if started_at.elapsed() > FORK_FAILOVER_TIMEOUT {
+                    self.events
+                        .emit(Event::ElectionTimedOut { epoch: self.epoch });
+                    self.role = ReplicaRole::Backup {
+                        primary_last_seen_at: Instant::now(),
+                    };
+                }
+            }
+        }
+    }
+
+    /// Transitions are taken on every call, the state is only replicated once per heartbeat
     pub fn update_state(&mut self) {
+        self.beat = self.heartbeat.beat();
+        if !self.is_primary() {
+            self.update_replication();
+            return;
+        }
         match &self.state {
+            ForkStateInternal::Unused if !self.may_grant() => (),
             ForkStateInternal::Unused => {
                 if let Some(next) = self.queue.pop_front() {
                     self.state = ForkStateInternal::Used {
                         thinker: next.thinker.clone(),
                         last_seen_at: next.last_seen_at,
                     };
+                    self.state_changed();
+                    self.holder_since = self.version;
+                    self.transceiver.metrics().increment(FORKS_GRANTED, &[]);
                     // self.transceiver.send(
                     //     ThinkerMessage::TakeForkAccepted(self.id.clone()),
                     //     &next.thinker.address,
                     // );
-                    log::info!("Fork taken by {}", next.thinker.id);
+                    self.events.emit(Event::ForkGranted {
+                        thinker: next.thinker.id,
+                    });
+                    if self.holder_confirmed() {
+                        self.notify_holder();
+                    }
                 }
             }
             ForkStateInternal::Used {
@@ -237,24 +723,201 @@ impl Fork {
             } => {
                 if last_seen_at.elapsed() > KEEP_ALIVE_TIMEOUT {
                     let thinker = thinker.clone();
-                    log::warn!(
-                        "No keep alive from thinker {}. Releasing fork access",
-                        thinker.id
-                    );
+                    self.events.emit(Event::HolderTimedOut {
+                        thinker: thinker.id,
+                    });
                     self.state = ForkStateInternal::Unused;
+                    self.state_changed();
+                    self.transceiver.metrics().increment(FORKS_TIMED_OUT, &[]);
                 }
             }
         }
+        self.update_replication();
+    }
+}
+
+impl Node for Fork {
+    type Message = ForkMessages;
+    type Init = InitForkParams;
+    type Config = ForkConfig;
+    type Checkpoint = ForkCheckpoint;
+
+    fn init_request(id: Id<Self>) -> InitMessages {
+        InitMessages::ForkRequest(id)
+    }
+
+    fn take_init(message: ForkMessages) -> Result<InitForkParams, ForkMessages> {
+        match message {
+            ForkMessages::Init(init_params) => Ok(init_params),
+            message => Err(message),
+        }
+    }
+
+    /// Replicas share the id of their fork, so the requested one is not used
+    fn config(_id: Id<Self>, address: SocketAddr, init: &InitForkParams) -> ForkConfig {
+        ForkConfig {
+            id: init.id.clone(),
+            visualizers: init.visualizers.clone(),
+            address,
+            replicas: init.replicas.clone(),
+            rank: init.rank,
+            seed: init.seed,
+        }
+    }
+
+    fn from_config(config: ForkConfig) -> (Id<Self>, InitForkParams) {
+        let init = InitForkParams {
+            id: config.id.clone(),
+            visualizers: config.visualizers,
+            replicas: config.replicas,
+            rank: config.rank,
+            seed: config.seed,
+        };
+        (config.id, init)
+    }
+
+    fn config_address(config: &ForkConfig) -> SocketAddr {
+        config.address
+    }
+
+    /// Replicas share the id of their fork, so the rank keeps their files apart
+    fn file_name(config: &ForkConfig) -> String {
+        match config.replicas.len() {
+            1 => format!("fork_{}", config.id.value),
+            _ => format!("fork_{}_{}", config.id.value, config.rank),
+        }
+    }
+
+    fn build(_id: Id<Self>, init: InitForkParams, setup: NodeSetup<Self>) -> Self {
+        Self::new(ForkInitParams {
+            id: init.id,
+            visualizers: init.visualizers,
+            transceiver: setup.transceiver,
+            unhandled_messages: setup.unhandled_messages,
+            replicas: init.replicas,
+            rank: init.rank,
+            checkpoint: setup.checkpoint,
+            checkpoint_file: setup.checkpoint_file,
+            control: setup.control,
+            event_log: setup.event_log,
+            seed: init.seed,
+        })
+    }
+
+    fn id(&self) -> &Id<Self> {
+        &self.id
+    }
+
+    fn print_started(&mut self) {
+        self.events.emit(Event::ForkStarted {
+            address: self.transceiver.local_address(),
+            rank: self.rank,
+            replicas: self.replicas.len(),
+        });
+    }
+
+    fn tick(&mut self, buffer: &mut [u8]) {
+        while let Some((message, entity)) = self.transceiver.receive::<ForkMessages>(buffer) {
+            if self.control.is_paused() && !matches!(message, ForkMessages::Control(_)) {
+                continue;
+            }
+            self.handle_message(message, entity);
+        }
+        match self.control.is_paused() {
+            // Only the heartbeat moves on, otherwise the past deadline wakes the node right away
+            true => self.beat = self.heartbeat.beat(),
+            false => self.update_state(),
+        }
+        self.save_checkpoint();
     }
 
-    pub fn update_visualizer(&self) {
-        if let Some(visualizer) = &self.visualizer {
+    /// Sends the state on every heartbeat and whenever it changed
+    fn update_visualizer(&mut self) {
+        self.subscribers.expire();
+        if !self.is_primary()
+            || self.control.is_paused()
+            || self.visualizers.is_empty() && self.subscribers.is_empty()
+        {
+            return;
+        }
+        let visualized = (
+            VisualizerForkState::from(&self.state),
+            self.queue
+                .iter()
+                .map(|queued| queued.thinker.id.clone())
+                .collect::<Vec<_>>(),
+        );
+        if !self.beat && self.visualized.as_ref() == Some(&visualized) {
+            return;
+        }
+        self.visualized = Some(visualized.clone());
+        let (state, queue) = visualized;
+        visualizer::notify(
+            self.visualizers.iter().chain(self.subscribers.iter()),
+            &self.transceiver,
+            VisualizerMessages::ForkStateChanged {
+                id: self.id.clone(),
+                state,
+                queue,
+            },
+        );
+    }
+
+    /// Timeouts are checked on every step, so only the heartbeat and delayed messages are timers
+    fn next_deadline(&self) -> Instant {
+        self.transceiver
+            .next_delivery()
+            .map_or(self.heartbeat.next_at(), |deliver_at| {
+                deliver_at.min(self.heartbeat.next_at())
+            })
+    }
+
+    fn wait(&mut self, loop_mode: LoopMode) {
+        let deadline = self.next_deadline();
+        loop_mode.wait(&mut self.transceiver, deadline);
+    }
+
+    fn should_crash(&mut self) -> CrashStatus {
+        let crash_status = self.control.should_crash();
+        self.transceiver.metrics().count_crash(&crash_status);
+        crash_status
+    }
+
+    fn reset(self) -> Self {
+        let checkpoint = self.checkpoint();
+        Self::new(ForkInitParams {
+            id: self.id,
+            transceiver: self.transceiver.reset(),
+            visualizers: self.visualizers,
+            unhandled_messages: vec![],
+            replicas: self.replicas,
+            rank: self.rank,
+            checkpoint,
+            checkpoint_file: self.checkpoint_file,
+            control: self.control,
+            event_log: self.events,
+            seed: None,
+        })
+    }
+
+    /// Replicated forks fail over to a backup that keeps holder and queue, so only unreplicated
+    /// forks tell their thinkers
+    fn shutdown(&mut self) {
+        self.events.emit(Event::ShuttingDown);
+        if !self.is_primary() || self.replicas.len() > 1 {
+            return;
+        }
+        let holder = match &self.state {
+            ForkStateInternal::Unused => None,
+            ForkStateInternal::Used { thinker, .. } => Some(thinker),
+        };
+        for thinker in holder
+            .into_iter()
+            .chain(self.queue.iter().map(|queued| &queued.thinker))
+        {
             self.transceiver.send(
-                VisualizerMessages::ForkStateChanged {
-                    id: self.id.clone(),
-                    state: (&self.state).into(),
-                },
-                &visualizer.address,
+                ThinkerMessage::ForkLeaving(self.id.clone()),
+                &thinker.address,
             );
         }
     }
@@ -0,0 +1,111 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::{Arc, Mutex};
+
+/// Just enough HTTP for local dashboards, every connection is served on its own thread
+#[derive(Debug)]
+pub enum Response {
+    Ok {
+        content_type: &'static str,
+        body: String,
+    },
+    /// Server sent events, the stream is written until the sender side is dropped or the client leaves
+    EventStream(Receiver<String>),
+    NotFound,
+}
+
+/// Fans server sent events out to every connected client
+#[derive(Debug, Clone, Default)]
+pub struct EventBroadcast {
+    subscribers: Arc<Mutex<Vec<Sender<String>>>>,
+}
+
+impl EventBroadcast {
+    pub fn subscribe(&self) -> Receiver<String> {
+        let (sender, receiver) = channel();
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    pub fn publish(&self, event: &str, data: &str) {
+        let message = format!("event: {event}\ndata: {data}\n\n");
+        // Clients that went away have dropped their receiver
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.send(message.clone()).is_ok());
+    }
+}
+
+/// Binds `address` and answers every `GET` with `handler(path)` on a background thread
+pub fn serve<F>(address: SocketAddr, handler: F) -> std::io::Result<SocketAddr>
+where
+    F: Fn(&str) -> Response + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind(address)?;
+    let local_address = listener.local_addr()?;
+    let handler = Arc::new(handler);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let handler = handler.clone();
+                    std::thread::spawn(move || {
+                        if let Err(e) = handle_connection(stream, handler.as_ref()) {
+                            log::debug!("HTTP connection failed: {e}");
+                        }
+                    });
+                }
+                Err(e) => log::warn!("Could not accept HTTP connection: {e}"),
+            }
+        }
+    });
+    Ok(local_address)
+}
+
+fn handle_connection<F>(mut stream: TcpStream, handler: &F) -> std::io::Result<()>
+where
+    F: Fn(&str) -> Response,
+{
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Headers are not needed, but have to be read before answering
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let response = match request_line
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["GET", target, ..] => handler(target.split('?').next().unwrap()),
+        _ => Response::NotFound,
+    };
+    match response {
+        Response::Ok { content_type, body } => write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        ),
+        Response::NotFound => write!(
+            stream,
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        ),
+        Response::EventStream(events) => {
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n"
+            )?;
+            stream.flush()?;
+            for event in events {
+                stream.write_all(event.as_bytes())?;
+                stream.flush()?;
+            }
+            Ok(())
+        }
+    }
+}
@@ -1,9 +1,70 @@
+pub mod control_messages;
 pub mod fork_messages;
 pub mod init_messages;
 pub mod thinker_messages;
 pub mod visualizer_messages;
 
+pub use control_messages::ControlMessage;
 pub use fork_messages::ForkMessages;
 pub use init_messages::InitMessages;
 pub use thinker_messages::ThinkerMessage;
 pub use visualizer_messages::VisualizerMessages;
+
+/// Name of the message variant, used as metrics label
+pub trait MessageKind {
+    fn kind(&self) -> &'static str;
+}
+
+impl MessageKind for ThinkerMessage {
+    fn kind(&self) -> &'static str {
+        match self {
+            ThinkerMessage::Init(_) => "Init",
+            ThinkerMessage::ForkAlive { .. } => "ForkAlive",
+            ThinkerMessage::ThinkerAliveRequest(_) => "ThinkerAliveRequest",
+            ThinkerMessage::ThinkerAliveResponse(_) => "ThinkerAliveResponse",
+            ThinkerMessage::Token(_) => "Token",
+            ThinkerMessage::TokenAliveBroadcast { .. } => "TokenAliveBroadcast",
+            ThinkerMessage::ProposeToken(_) => "ProposeToken",
+            ThinkerMessage::Leaving(_) => "Leaving",
+            ThinkerMessage::ForkLeaving(_) => "ForkLeaving",
+            ThinkerMessage::Control(_) => "Control",
+            ThinkerMessage::Subscribe { .. } => "Subscribe",
+        }
+    }
+}
+
+impl MessageKind for ForkMessages {
+    fn kind(&self) -> &'static str {
+        match self {
+            ForkMessages::Init(_) => "Init",
+            ForkMessages::KeepAlive(_) => "KeepAlive",
+            ForkMessages::Release(_) => "Release",
+            ForkMessages::Replicate { .. } => "Replicate",
+            ForkMessages::ReplicateAck { .. } => "ReplicateAck",
+            ForkMessages::RequestVote { .. } => "RequestVote",
+            ForkMessages::Vote { .. } => "Vote",
+            ForkMessages::Control(_) => "Control",
+            ForkMessages::Subscribe { .. } => "Subscribe",
+        }
+    }
+}
+
+impl MessageKind for InitMessages {
+    fn kind(&self) -> &'static str {
+        match self {
+            InitMessages::ForkRequest(_) => "ForkRequest",
+            InitMessages::ThinkerRequest(_) => "ThinkerRequest",
+            InitMessages::VisualizerRequest { .. } => "VisualizerRequest",
+        }
+    }
+}
+
+impl MessageKind for VisualizerMessages {
+    fn kind(&self) -> &'static str {
+        match self {
+            VisualizerMessages::Init { .. } => "Init",
+            VisualizerMessages::ForkStateChanged { .. } => "ForkStateChanged",
+            VisualizerMessages::ThinkerStateChanged { .. } => "ThinkerStateChanged",
+        }
+    }
+}
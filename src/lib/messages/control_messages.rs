@@ -0,0 +1,29 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use rkyv::{Archive, Deserialize, Serialize};
+
+#[derive(Archive, Serialize, Deserialize, Debug, Clone)]
+pub enum ControlMessage {
+    Crash {
+        duration: Duration,
+    },
+    CrashPermanently,
+    /// Paused nodes ignore every message except control messages and stop updating their state
+    Pause,
+    Resume,
+    /// Fraction of outgoing messages that gets dropped, replaces the default message loss
+    DropMessages {
+        percentage: f64,
+    },
+    RandomCrashes(bool),
+    /// Cuts the links of the receiving node to the given addresses until healed
+    Partition {
+        name: String,
+        blocked: Vec<SocketAddr>,
+    },
+    /// Heals the named partition or all partitions
+    Heal {
+        name: Option<String>,
+    },
+}
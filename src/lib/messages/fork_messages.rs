@@ -1,5 +1,9 @@
+use std::net::SocketAddr;
+
 use rkyv::{Archive, Deserialize, Serialize};
 
+use crate::lib::fork::{Fork, ReplicaVersion, ReplicatedForkState};
+use crate::lib::messages::ControlMessage;
 use crate::lib::thinker::Thinker;
 use crate::lib::utils::Id;
 use crate::lib::visualizer::VisualizerRef;
@@ -11,8 +15,43 @@ pub enum ThinkerForkState {
 
 #[derive(Archive, Serialize, Deserialize, Debug)]
 pub enum ForkMessages {
-    Init(Option<VisualizerRef>),
+    Init(InitForkParams),
     /// Used aquire the lock and keep it alive
     KeepAlive(Id<Thinker>),
     Release(Id<Thinker>),
+    /// Sent by the primary replica every tick, doubles as its heartbeat
+    Replicate {
+        epoch: u32,
+        /// Echoed by the ack, the primary dates its lease by when the round was sent
+        round: u32,
+        state: ReplicatedForkState,
+    },
+    ReplicateAck {
+        epoch: u32,
+        round: u32,
+        version: ReplicaVersion,
+    },
+    RequestVote {
+        epoch: u32,
+        version: ReplicaVersion,
+    },
+    Vote {
+        epoch: u32,
+    },
+    Control(ControlMessage),
+    /// Adds the sender as visualizer or replaces its filter, sent to every replica
+    Subscribe {
+        kinds: Vec<String>,
+    },
+}
+
+#[derive(Archive, Serialize, Deserialize, Debug)]
+pub struct InitForkParams {
+    pub id: Id<Fork>,
+    pub visualizers: Vec<VisualizerRef>,
+    /// Addresses of all replicas of this fork ordered by rank, rank 0 starts as primary
+    pub replicas: Vec<SocketAddr>,
+    pub rank: usize,
+    /// Makes random crashes reproducible
+    pub seed: Option<u64>,
 }
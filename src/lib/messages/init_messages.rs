@@ -8,5 +8,5 @@ use crate::lib::utils::Id;
 pub enum InitMessages {
     ForkRequest(Id<Fork>),
     ThinkerRequest(Id<Thinker>),
-    VisualizerRequest,
+    VisualizerRequest { kinds: Vec<String> },
 }
@@ -1,12 +1,13 @@
 use rkyv::{Archive, Deserialize, Serialize};
 
 use crate::lib::fork::{Fork, ForkRef};
+use crate::lib::messages::ControlMessage;
 use crate::lib::thinker::{Thinker, ThinkerRef};
 use crate::lib::utils::{EntityType, Id};
 use crate::lib::visualizer::VisualizerRef;
 
 // TODO: Remove clone if possible, some weird borrow shit :(
-#[derive(Archive, Serialize, Deserialize, Debug, Clone)]
+#[derive(Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct Token {
     pub id: Id<Token>,
     pub version: u32,
@@ -49,7 +50,7 @@ pub enum TokenPriority {
     Low,
 }
 
-#[derive(Archive, Serialize, Deserialize, Debug, Clone)]
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct TokenRef {
     pub id: Id<Token>,
     pub version: u32,
@@ -108,7 +109,7 @@ pub enum ForkState {
 
 #[derive(Archive, Serialize, Deserialize, Debug)]
 pub enum ThinkerMessage {
-    Init(InitThinkerParams),
+    Init(Box<InitThinkerParams>),
     ForkAlive {
         id: Id<Fork>,
         state: ForkState,
@@ -121,13 +122,28 @@ pub enum ThinkerMessage {
         broadcast_issuer: Id<Thinker>,
     },
     ProposeToken(TokenProposal),
+    /// Sent to the predecessors by a thinker that shuts down
+    Leaving(Id<Thinker>),
+    /// Sent to its holder and queue by an unreplicated fork that shuts down
+    ForkLeaving(Id<Fork>),
+    Control(ControlMessage),
+    /// Adds the sender as visualizer or replaces its filter, lets visualizers join a running ring
+    Subscribe {
+        kinds: Vec<String>,
+    },
 }
 
 #[derive(Archive, Serialize, Deserialize, Debug)]
 pub struct InitThinkerParams {
+    /// Assigned by the init server, replaces the id the thinker joined with
+    pub id: Id<Thinker>,
     pub token: Option<Token>,
     pub forks: [ForkRef; 2],
     pub next_thinkers: Vec<ThinkerRef>,
-    pub visualizer: Option<VisualizerRef>,
+    /// Thinkers that have this one among their next thinkers, told when it leaves
+    pub previous_thinkers: Vec<ThinkerRef>,
+    pub visualizers: Vec<VisualizerRef>,
     pub available_tokens: Vec<TokenRef>,
+    /// Makes meal timing and random crashes reproducible
+    pub seed: Option<u64>,
 }
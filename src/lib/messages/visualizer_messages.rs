@@ -7,7 +7,7 @@ use crate::lib::messages::thinker_messages::TokenRef;
 use crate::lib::thinker::{Thinker, ThinkerRef};
 use crate::lib::utils::Id;
 
-#[derive(Archive, Serialize, Deserialize, Debug)]
+#[derive(Archive, Serialize, Deserialize, Debug, Clone)]
 pub enum VisualizerMessages {
     Init {
         thinkers: Vec<ThinkerRef>,
@@ -16,21 +16,24 @@ pub enum VisualizerMessages {
     ForkStateChanged {
         id: Id<Fork>,
         state: VisualizerForkState,
+        queue: Vec<Id<Thinker>>,
     },
     ThinkerStateChanged {
         id: Id<Thinker>,
+        /// Lets visualizers without an init message place the thinker in the ring
+        forks: [ForkRef; 2],
         state: VisualizerThinkerState,
         token_state: Vec<VisualizerThinkerAvailableTokenState>,
     },
 }
 
-#[derive(Archive, Serialize, Deserialize, Debug)]
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum VisualizerForkState {
     Unused,
     Used(Id<Thinker>),
 }
 
-#[derive(Archive, Serialize, Deserialize, Debug)]
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum VisualizerThinkerState {
     Thinking,
     Hungry,
@@ -38,7 +41,7 @@ pub enum VisualizerThinkerState {
     Eating { token: TokenRef },
 }
 
-#[derive(Archive, Serialize, Deserialize, Debug)]
+#[derive(Archive, Serialize, Deserialize, Debug, Clone)]
 pub enum VisualizerThinkerAvailableTokenState {
     Passive {
         not_seen_for: Duration,
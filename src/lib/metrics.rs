@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use crate::CrashStatus;
+use crate::lib::http::{self, Response};
+
+const PREFIX: &str = "philosophers_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Counter {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub labels: &'static [&'static str],
+}
+
+pub const MESSAGES_SENT: Counter = Counter {
+    name: "messages_sent_total",
+    help: "Messages handed to the transport, by message type",
+    labels: &["type"],
+};
+pub const MESSAGES_DROPPED: Counter = Counter {
+    name: "messages_dropped_total",
+    help: "Messages dropped by simulated loss, partitions or because they did not decode, by message type and reason",
+    labels: &["type", "reason"],
+};
+pub const MESSAGES_RECEIVED: Counter = Counter {
+    name: "messages_received_total",
+    help: "Messages delivered to the node, by message type",
+    labels: &["type"],
+};
+pub const TOKENS_PASSED: Counter = Counter {
+    name: "tokens_passed_total",
+    help: "Tokens passed on to the next thinker",
+    labels: &[],
+};
+pub const PROPOSALS_STARTED: Counter = Counter {
+    name: "proposals_started_total",
+    help: "Token proposals started after a token timed out",
+    labels: &[],
+};
+pub const FORKS_GRANTED: Counter = Counter {
+    name: "forks_granted_total",
+    help: "Fork grants to a queued thinker",
+    labels: &[],
+};
+pub const FORKS_TIMED_OUT: Counter = Counter {
+    name: "forks_timed_out_total",
+    help: "Fork grants revoked because the holder stopped sending keep alives",
+    labels: &[],
+};
+pub const MEALS: Counter = Counter {
+    name: "meals_total",
+    help: "Finished meals",
+    labels: &[],
+};
+pub const CRASHES: Counter = Counter {
+    name: "crashes_total",
+    help: "Simulated crashes, by kind",
+    labels: &["kind"],
+};
+
+const COUNTERS: [Counter; 9] = [
+    MESSAGES_SENT,
+    MESSAGES_DROPPED,
+    MESSAGES_RECEIVED,
+    TOKENS_PASSED,
+    PROPOSALS_STARTED,
+    FORKS_GRANTED,
+    FORKS_TIMED_OUT,
+    MEALS,
+    CRASHES,
+];
+
+type Labels = Vec<(&'static str, String)>;
+
+/// Counters of one node, cheap to clone and shared with the metrics endpoint
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    counters: Arc<Mutex<BTreeMap<Counter, BTreeMap<Labels, u64>>>>,
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+impl Metrics {
+    pub fn increment(&self, counter: Counter, labels: &[(&'static str, &str)]) {
+        let labels = labels
+            .iter()
+            .map(|(name, value)| (*name, value.to_string()))
+            .collect();
+        *self
+            .counters
+            .lock()
+            .unwrap()
+            .entry(counter)
+            .or_default()
+            .entry(labels)
+            .or_default() += 1;
+    }
+
+    pub fn count_crash(&self, crash_status: &CrashStatus) {
+        match crash_status {
+            CrashStatus::Continue => (),
+            CrashStatus::Crash(_) => self.increment(CRASHES, &[("kind", "temporary")]),
+            CrashStatus::PermanentCrash => self.increment(CRASHES, &[("kind", "permanent")]),
+        }
+    }
+
+    /// Prometheus text exposition format, counters without labels are reported from zero
+    pub fn render(&self) -> String {
+        let counters = self.counters.lock().unwrap();
+        let mut output = String::new();
+        for counter in COUNTERS {
+            let name = format!("{PREFIX}{}", counter.name);
+            writeln!(output, "# HELP {name} {}", counter.help).unwrap();
+            writeln!(output, "# TYPE {name} counter").unwrap();
+            match counters.get(&counter) {
+                Some(values) => {
+                    for (labels, value) in values {
+                        match labels.is_empty() {
+                            true => writeln!(output, "{name} {value}").unwrap(),
+                            false => writeln!(
+                                output,
+                                "{name}{{{}}} {value}",
+                                labels
+                                    .iter()
+                                    .map(|(label, value)| format!("{label}=\"{}\"", escape(value)))
+                                    .collect::<Vec<_>>()
+                                    .join(",")
+                            )
+                            .unwrap(),
+                        }
+                    }
+                }
+                None if counter.labels.is_empty() => writeln!(output, "{name} 0").unwrap(),
+                None => (),
+            }
+        }
+        output
+    }
+
+    /// Serves `/metrics` on a background thread, returns the bound address
+    pub fn serve(&self, address: SocketAddr) -> std::io::Result<SocketAddr> {
+        let metrics = self.clone();
+        http::serve(address, move |path| match path {
+            "/metrics" => Response::Ok {
+                content_type: "text/plain; version=0.0.4",
+                body: metrics.render(),
+            },
+            _ => Response::NotFound,
+        })
+    }
+}
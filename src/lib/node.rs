@@ -0,0 +1,70 @@
+use std::fmt::Debug;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use rkyv::api::high::HighValidator;
+use rkyv::bytecheck::CheckBytes;
+use rkyv::de::Pool;
+use rkyv::rancor::Strategy;
+use rkyv::{Archive, Deserialize};
+
+use crate::CrashStatus;
+use crate::lib::config::Config;
+use crate::lib::control::NodeControl;
+use crate::lib::events::EventLog;
+use crate::lib::messages::{InitMessages, MessageKind};
+use crate::lib::runner::LoopMode;
+use crate::lib::transceiver::Transceiver;
+use crate::lib::utils::{EntityType, Id};
+
+/// Everything a node is built from besides its role specific init params
+#[derive(Debug)]
+pub struct NodeSetup<N: Node> {
+    pub transceiver: Transceiver,
+    /// Received before the init params, handled once the node runs
+    pub unhandled_messages: Vec<(N::Message, SocketAddr)>,
+    pub checkpoint: Option<N::Checkpoint>,
+    pub checkpoint_file: Option<PathBuf>,
+    pub control: NodeControl,
+    pub event_log: EventLog,
+}
+
+/// Role in the ring, started and kept alive by `runner::run_node`
+pub trait Node: EntityType + Sized {
+    /// Messages the node receives, one of them carries the init params
+    type Message: Archive<
+            Archived: for<'a> CheckBytes<HighValidator<'a, rkyv::rancor::Error>>
+                          + Deserialize<Self::Message, Strategy<Pool, rkyv::rancor::Error>>,
+        > + MessageKind
+        + Debug;
+    /// Role specific init params sent by the init server
+    type Init;
+    /// Written after init, restarts the node without the init server
+    type Config: Config;
+    type Checkpoint: Config;
+
+    /// Sent to the init server to join the ring
+    fn init_request(id: Id<Self>) -> InitMessages;
+    /// The init params if the message carries them, otherwise it is handled once the node runs
+    fn take_init(message: Self::Message) -> Result<Self::Init, Self::Message>;
+    fn config(id: Id<Self>, address: SocketAddr, init: &Self::Init) -> Self::Config;
+    fn from_config(config: Self::Config) -> (Id<Self>, Self::Init);
+    fn config_address(config: &Self::Config) -> SocketAddr;
+    /// Name of the config and checkpoint files without extension
+    fn file_name(config: &Self::Config) -> String;
+    /// `id` is the one the node joined with
+    fn build(id: Id<Self>, init: Self::Init, setup: NodeSetup<Self>) -> Self;
+
+    fn id(&self) -> &Id<Self>;
+    fn print_started(&mut self);
+    fn tick(&mut self, buffer: &mut [u8]);
+    fn update_visualizer(&mut self);
+    fn next_deadline(&self) -> Instant;
+    fn wait(&mut self, loop_mode: LoopMode);
+    fn should_crash(&mut self) -> CrashStatus;
+    /// Restarts the node after a crash, only what the node keeps across crashes survives
+    fn reset(self) -> Self;
+    /// Called once before a graceful exit, hands on what other nodes would otherwise wait for
+    fn shutdown(&mut self);
+}
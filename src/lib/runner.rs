@@ -0,0 +1,261 @@
+use std::net::{SocketAddr, UdpSocket};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::sleep;
+use std::time::Instant;
+
+use clap::{Parser, Subcommand};
+use signal_hook::consts::{SIGINT, SIGTERM};
+
+use crate::lib::clock::SharedClock;
+use crate::lib::config::Config;
+use crate::lib::control::NodeControl;
+use crate::lib::events::EventLog;
+use crate::lib::faults::FaultConfig;
+use crate::lib::metrics::Metrics;
+use crate::lib::node::{Node, NodeSetup};
+use crate::lib::transceiver::Transceiver;
+use crate::lib::utils::Id;
+use crate::{CrashStatus, NETWORK_BUFFER_SIZE, TICK_INTERVAL};
+
+#[derive(Subcommand, Debug)]
+pub enum NodeStart {
+    /// Restarts a node from the config it saved on init
+    Config { config_file: PathBuf },
+    /// Joins the ring through the init server
+    InitServer {
+        address: SocketAddr,
+        #[arg(short, long)]
+        save_config_dir: Option<PathBuf>,
+        #[arg(short, long)]
+        init_server: SocketAddr,
+    },
+}
+
+/// Command line of the node binaries, the same for every role
+#[derive(Parser, Debug)]
+pub struct NodeCli {
+    #[command(subcommand)]
+    pub command: NodeStart,
+    /// Only crash when told so by the `ctl` binary
+    #[arg(long, global = true)]
+    pub no_random_crashes: bool,
+    /// Network fault config applied to everything this node sends and receives
+    #[arg(long, global = true)]
+    pub faults: Option<PathBuf>,
+    /// Serves Prometheus metrics on `http://<address>/metrics`
+    #[arg(long, global = true)]
+    pub metrics: Option<SocketAddr>,
+    /// Appends every state transition as JSON line to this file
+    #[arg(long, global = true)]
+    pub event_log: Option<PathBuf>,
+    /// Stamps messages and logged events with vector clocks, see the `timeline` binary
+    #[arg(long, global = true)]
+    pub vector_clock: bool,
+    /// Sleeps a fixed tick between steps instead of waking on messages and timers
+    #[arg(long, global = true)]
+    pub tick_mode: bool,
+}
+
+/// How a node waits between two steps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoopMode {
+    /// Wakes as soon as a message arrives or the next timer of the state machine is due
+    #[default]
+    Event,
+    /// Sleeps `TICK_INTERVAL` after every step, so every hop waits for the next tick
+    Tick,
+}
+
+impl LoopMode {
+    pub fn wait(&self, transceiver: &mut Transceiver, deadline: Instant) {
+        match self {
+            LoopMode::Event => transceiver.wait(deadline),
+            LoopMode::Tick => sleep(TICK_INTERVAL),
+        }
+    }
+}
+
+/// Paces periodic work like keep alives to `TICK_INTERVAL`, however often the node is stepped
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    next_at: Instant,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self {
+            next_at: Instant::now(),
+        }
+    }
+}
+
+impl Heartbeat {
+    /// True at most once per `TICK_INTERVAL`
+    pub fn beat(&mut self) -> bool {
+        let now = Instant::now();
+        if now < self.next_at {
+            return false;
+        }
+        self.next_at = now + TICK_INTERVAL;
+        true
+    }
+
+    pub fn next_at(&self) -> Instant {
+        self.next_at
+    }
+}
+
+/// Set by SIGINT and SIGTERM, a second signal exits right away
+fn shutdown_flag() -> Arc<AtomicBool> {
+    let flag = Arc::new(AtomicBool::new(false));
+    for signal in [SIGINT, SIGTERM] {
+        signal_hook::flag::register_conditional_shutdown(signal, 1, flag.clone())
+            .and_then(|_| signal_hook::flag::register(signal, flag.clone()))
+            .unwrap_or_else(|e| panic!("Could not register handler for signal {signal}: {e}"));
+    }
+    flag
+}
+
+/// Starts the node and restarts it after every crash, returns once it crashed permanently or
+/// shut down on a signal
+pub fn run_node<N: Node>(cli: NodeCli) {
+    let shutdown = shutdown_flag();
+    let mut buffer = [0; NETWORK_BUFFER_SIZE];
+    let loop_mode = match cli.tick_mode {
+        true => LoopMode::Tick,
+        false => LoopMode::Event,
+    };
+    let mut node = start_node::<N>(cli, &mut buffer);
+    loop {
+        node.print_started();
+        loop {
+            node.tick(&mut buffer);
+            node.update_visualizer();
+            if shutdown.load(Ordering::Relaxed) {
+                node.shutdown();
+                return;
+            }
+            node.wait(loop_mode);
+            match node.should_crash() {
+                CrashStatus::Continue => (),
+                CrashStatus::Crash(crash_duration) => {
+                    log::info!("{} crashed. Restarting in {:?}", node.id(), crash_duration);
+                    // A crashed node holds nothing to hand on, it exits right away
+                    let restart_at = Instant::now() + crash_duration;
+                    while Instant::now() < restart_at {
+                        if shutdown.load(Ordering::Relaxed) {
+                            return;
+                        }
+                        sleep(
+                            restart_at
+                                .saturating_duration_since(Instant::now())
+                                .min(TICK_INTERVAL),
+                        );
+                    }
+                    node = node.reset();
+                    break;
+                }
+                CrashStatus::PermanentCrash => {
+                    log::error!("{} permanently crashed", node.id());
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Gets the init params from the config or the init server and builds the node
+fn start_node<N: Node>(cli: NodeCli, buffer: &mut [u8]) -> N {
+    let fault_config = cli
+        .faults
+        .as_ref()
+        .map(|path| FaultConfig::read(path).unwrap_or_else(|e| panic!("{e}")))
+        .unwrap_or_default();
+    let event_log = cli
+        .event_log
+        .as_ref()
+        .map(|path| {
+            EventLog::open(path)
+                .unwrap_or_else(|e| panic!("Could not open {}: {e}", path.display()))
+        })
+        .unwrap_or_default();
+    let bind = |address| {
+        let mut transceiver = Transceiver::new(UdpSocket::bind(address).unwrap());
+        transceiver.set_fault_config(fault_config.clone());
+        transceiver
+    };
+    let mut unhandled_messages = vec![];
+    let (id, init, mut transceiver, checkpoint, checkpoint_file) = match cli.command {
+        NodeStart::Config { config_file } => {
+            let config = N::Config::read(&config_file);
+            let checkpoint_file = config_file.with_extension("checkpoint");
+            let checkpoint = checkpoint_file
+                .exists()
+                .then(|| N::Checkpoint::read(&checkpoint_file));
+            if checkpoint.is_some() {
+                log::info!("Restored checkpoint {}", checkpoint_file.display());
+            }
+            let transceiver = bind(N::config_address(&config));
+            let (id, init) = N::from_config(config);
+            (id, init, transceiver, checkpoint, Some(checkpoint_file))
+        }
+        NodeStart::InitServer {
+            address,
+            save_config_dir,
+            init_server,
+        } => {
+            let mut transceiver = bind(address);
+            let id = Id::random();
+
+            transceiver.send_reliable(N::init_request(id.clone()), &init_server);
+            let init = 'outer: loop {
+                while let Some((message, entity)) = transceiver.receive::<N::Message>(buffer) {
+                    match N::take_init(message) {
+                        Ok(init) => break 'outer init,
+                        Err(message) => unhandled_messages.push((message, entity)),
+                    }
+                }
+                transceiver.wait(Instant::now() + TICK_INTERVAL);
+            };
+
+            let config = N::config(id.clone(), transceiver.local_address(), &init);
+            let file_name = N::file_name(&config);
+            let checkpoint_file = save_config_dir
+                .as_ref()
+                .map(|path| path.join(format!("{file_name}.checkpoint")));
+            if let Some(path) = save_config_dir {
+                config.write(&path.join(format!("{file_name}.conf")));
+            }
+            (id, init, transceiver, None, checkpoint_file)
+        }
+    };
+
+    if cli.vector_clock {
+        transceiver.set_clock(SharedClock::new(id.value));
+    }
+    if let Some(address) = cli.metrics {
+        serve_metrics(transceiver.metrics(), address);
+    }
+    N::build(
+        id,
+        init,
+        NodeSetup {
+            transceiver,
+            unhandled_messages,
+            checkpoint,
+            checkpoint_file,
+            control: NodeControl::new(!cli.no_random_crashes),
+            event_log,
+        },
+    )
+}
+
+/// Serves `--metrics` of a binary, the init server and visualizer use it as well
+pub fn serve_metrics(metrics: &Metrics, address: SocketAddr) {
+    let address = metrics
+        .serve(address)
+        .unwrap_or_else(|e| panic!("Could not serve metrics on {address}: {e}"));
+    log::info!("Serving metrics on http://{address}/metrics");
+}
@@ -1,27 +1,53 @@
 use std::net::SocketAddr;
-use std::time::Instant;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
-use rand::Rng;
-use rand::rngs::ThreadRng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use rkyv::{Archive, Deserialize, Serialize};
 
+use crate::lib::config::{Config, ThinkerConfig};
+use crate::lib::control::NodeControl;
+use crate::lib::events::{Event, EventLog};
 use crate::lib::fork::ForkRef;
 use crate::lib::messages::thinker_messages::{
-    ForkState, Token, TokenPriority, TokenProposal, TokenRef,
+    ForkState, InitThinkerParams, Token, TokenPriority, TokenProposal, TokenRef,
 };
 use crate::lib::messages::visualizer_messages::{
     VisualizerThinkerAvailableTokenState, VisualizerThinkerState,
 };
-use crate::lib::messages::{ForkMessages, ThinkerMessage, VisualizerMessages};
+use crate::lib::messages::{ForkMessages, InitMessages, ThinkerMessage, VisualizerMessages};
+use crate::lib::metrics::{MEALS, PROPOSALS_STARTED, TOKENS_PASSED};
+use crate::lib::node::{Node, NodeSetup};
+use crate::lib::runner::{Heartbeat, LoopMode};
 use crate::lib::transceiver::Transceiver;
 use crate::lib::utils::{EntityType, Id};
-use crate::lib::visualizer::VisualizerRef;
+use crate::lib::visualizer::{self, Subscribers, VisualizerRef};
 use crate::{
-    KEEP_ALIVE_TIMEOUT, KEEP_TOKEN_ALIVE_TIMEOUT, MAX_EATING_TIME, MAX_THINKING_TIME,
+    CrashStatus, KEEP_ALIVE_TIMEOUT, KEEP_TOKEN_ALIVE_TIMEOUT, MAX_EATING_TIME, MAX_THINKING_TIME,
     MIN_EATING_TIME, MIN_THINKING_TIME,
 };
 
-#[derive(Archive, Serialize, Deserialize, Clone, Debug)]
+/// How long thinkers think and eat, drawn uniformly from the ranges
+#[derive(Debug, Clone)]
+pub struct MealTiming {
+    pub thinking: RangeInclusive<Duration>,
+    pub eating: RangeInclusive<Duration>,
+}
+
+impl Default for MealTiming {
+    fn default() -> Self {
+        Self {
+            thinking: MIN_THINKING_TIME..=MAX_THINKING_TIME,
+            eating: MIN_EATING_TIME..=MAX_EATING_TIME,
+        }
+    }
+}
+
+#[derive(
+    Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq,
+)]
 pub struct ThinkerRef {
     pub address: SocketAddr,
     pub id: Id<Thinker>,
@@ -53,6 +79,7 @@ enum ThinkerState {
     },
     Eating {
         token: Token,
+        started_eating_at: Instant,
         stop_eating_at: Instant,
         fork_last_seen_at: [Instant; 2],
     },
@@ -85,6 +112,13 @@ impl ThinkerRefLastSeen {
     fn is_timed_out(&self) -> bool {
         self.last_seen_at.elapsed() > KEEP_ALIVE_TIMEOUT
     }
+
+    /// Counts as timed out until the thinker answers an alive request again
+    fn mark_left(&mut self) {
+        if let Some(at) = Instant::now().checked_sub(KEEP_ALIVE_TIMEOUT * 2) {
+            self.last_seen_at = at;
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -121,15 +155,42 @@ impl TokenRefLastSeen {
     }
 }
 
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TokenCheckpoint {
+    pub token_ref: TokenRef,
+    pub current_proposal_version: u32,
+}
+
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct MealStatistics {
+    pub meals: u32,
+    pub eating_time: Duration,
+}
+
+/// Knowledge of a thinker that has to survive a crash, so a recovered node does not send stale proposals
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ThinkerCheckpoint {
+    pub available_tokens: Vec<TokenCheckpoint>,
+    pub meal_statistics: MealStatistics,
+}
+
 pub struct ThinkerInitParams {
     pub id: Id<Thinker>,
     pub transceiver: Transceiver,
     pub unhandled_messages: Vec<(ThinkerMessage, SocketAddr)>,
     pub forks: [ForkRef; 2],
     pub next_thinkers: Vec<ThinkerRef>,
+    pub previous_thinkers: Vec<ThinkerRef>,
     pub token: Option<Token>,
     pub available_tokens: Vec<TokenRef>,
-    pub visualizer: Option<VisualizerRef>,
+    pub visualizers: Vec<VisualizerRef>,
+    pub checkpoint: Option<ThinkerCheckpoint>,
+    pub checkpoint_file: Option<PathBuf>,
+    pub control: NodeControl,
+    pub event_log: EventLog,
+    pub timing: MealTiming,
+    /// Seeds meal timing and random crashes, from the thread rng if not set
+    pub seed: Option<u64>,
 }
 
 #[derive(Debug)]
@@ -139,13 +200,42 @@ pub struct Thinker {
     state: ThinkerState,
     forks: [ForkRef; 2],
     next_thinkers: Vec<ThinkerRefLastSeen>,
-    rng: ThreadRng,
-    visualizer: Option<VisualizerRef>,
+    /// Told when this thinker leaves, so they stop passing it tokens
+    previous_thinkers: Vec<ThinkerRef>,
+    /// Seeded from the thread rng or the init seed, unlike it the thinker stays `Send`
+    rng: StdRng,
+    /// Kept to reseed the thinker reproducibly after a crash
+    seeded: bool,
+    visualizers: Vec<VisualizerRef>,
+    subscribers: Subscribers,
     available_tokens: Vec<TokenRefLastSeen>,
+    meal_statistics: MealStatistics,
+    checkpoint_file: Option<PathBuf>,
+    last_checkpoint: Option<ThinkerCheckpoint>,
+    control: NodeControl,
+    events: EventLog,
+    timing: MealTiming,
+    heartbeat: Heartbeat,
+    /// Whether the last step was a heartbeat, only then keep alives and broadcasts are sent
+    beat: bool,
+    /// Last state sent to the visualizers, changes are sent right away
+    visualized: Option<VisualizerThinkerState>,
 }
 impl Thinker {
-    pub fn new(init_params: ThinkerInitParams) -> Self {
-        let mut rng = rand::rng();
+    pub fn new(mut init_params: ThinkerInitParams) -> Self {
+        let mut rng = match init_params.seed {
+            Some(seed) => {
+                let mut rng = StdRng::seed_from_u64(seed);
+                init_params.control.seed(rng.random());
+                rng
+            }
+            None => StdRng::from_rng(&mut rand::rng()),
+        };
+        init_params.event_log.set_node(init_params.id.value);
+        if let Some(clock) = init_params.transceiver.clock() {
+            clock.set_node(init_params.id.value);
+            init_params.event_log.set_clock(clock.clone());
+        }
 
         if let Some(token) = init_params.token {
             init_params.transceiver.send(
@@ -158,7 +248,7 @@ impl Thinker {
             transceiver: init_params.transceiver,
             state: ThinkerState::Thinking {
                 stop_thinking_at: Instant::now()
-                    + rng.random_range(MIN_THINKING_TIME..=MAX_THINKING_TIME),
+                    + rng.random_range(init_params.timing.thinking.clone()),
             },
             forks: init_params.forks,
             next_thinkers: init_params
@@ -169,18 +259,58 @@ impl Thinker {
                     last_seen_at: Instant::now(),
                 })
                 .collect(),
+            previous_thinkers: init_params.previous_thinkers,
             rng,
-            visualizer: init_params.visualizer,
+            seeded: init_params.seed.is_some(),
+            visualizers: init_params.visualizers,
+            subscribers: Subscribers::default(),
             available_tokens: init_params
                 .available_tokens
                 .into_iter()
-                .map(|token_ref| TokenRefLastSeen {
-                    current_token_ref: token_ref,
-                    last_seen_at: Instant::now(),
-                    state: TokenRefLastSeenState::Passive,
-                    current_proposal_version: 0,
+                .map(|token_ref| {
+                    let restored = init_params.checkpoint.as_ref().and_then(|checkpoint| {
+                        checkpoint
+                            .available_tokens
+                            .iter()
+                            .find(|restored| restored.token_ref.id.eq(&token_ref.id))
+                    });
+                    match restored {
+                        Some(restored) => TokenRefLastSeen {
+                            current_token_ref: match token_ref
+                                .priority(&restored.token_ref)
+                                .unwrap()
+                            {
+                                TokenPriority::High => token_ref,
+                                TokenPriority::Equal | TokenPriority::Low => {
+                                    restored.token_ref.clone()
+                                }
+                            },
+                            last_seen_at: Instant::now(),
+                            state: TokenRefLastSeenState::Passive,
+                            current_proposal_version: restored.current_proposal_version,
+                        },
+                        None => TokenRefLastSeen {
+                            current_token_ref: token_ref,
+                            last_seen_at: Instant::now(),
+                            state: TokenRefLastSeenState::Passive,
+                            current_proposal_version: 0,
+                        },
+                    }
                 })
                 .collect(),
+            meal_statistics: init_params
+                .checkpoint
+                .as_ref()
+                .map(|checkpoint| checkpoint.meal_statistics.clone())
+                .unwrap_or_default(),
+            last_checkpoint: init_params.checkpoint,
+            checkpoint_file: init_params.checkpoint_file,
+            control: init_params.control,
+            events: init_params.event_log,
+            timing: init_params.timing,
+            heartbeat: Heartbeat::default(),
+            beat: false,
+            visualized: None,
         };
         init_params
             .unhandled_messages
@@ -191,33 +321,33 @@ impl Thinker {
         thinker
     }
 
-    pub fn reset(self) -> Self {
-        Self::new(ThinkerInitParams {
-            id: self.id,
-            transceiver: self.transceiver.reset(),
-            unhandled_messages: vec![],
-            forks: self.forks,
-            next_thinkers: self
-                .next_thinkers
-                .into_iter()
-                .map(|el| el.thinker)
-                .collect(),
-            token: None,
+    pub fn state(&self) -> VisualizerThinkerState {
+        VisualizerThinkerState::from(&self.state)
+    }
+
+    pub fn checkpoint(&self) -> ThinkerCheckpoint {
+        ThinkerCheckpoint {
             available_tokens: self
                 .available_tokens
-                .into_iter()
-                .map(|el| el.current_token_ref)
+                .iter()
+                .map(|last_seen| TokenCheckpoint {
+                    token_ref: last_seen.current_token_ref.clone(),
+                    current_proposal_version: last_seen.current_proposal_version,
+                })
                 .collect(),
-            visualizer: self.visualizer,
-        })
+            meal_statistics: self.meal_statistics.clone(),
+        }
     }
 
-    pub fn print_started(&self) {
-        log::info!(
-            "Started Thinker {} {}",
-            self.transceiver.local_address(),
-            self.id,
-        )
+    fn save_checkpoint(&mut self) {
+        let Some(checkpoint_file) = &self.checkpoint_file else {
+            return;
+        };
+        let checkpoint = self.checkpoint();
+        if self.last_checkpoint.as_ref() != Some(&checkpoint) {
+            checkpoint.write_atomic(checkpoint_file);
+            self.last_checkpoint = Some(checkpoint);
+        }
     }
 
     fn token_broadcast(&self, token_ref: TokenRef, broadcast_issuer: Id<Thinker>) {
@@ -252,6 +382,7 @@ impl Thinker {
         {
             self.transceiver
                 .send(ThinkerMessage::Token(token), &next_thinker.address);
+            self.transceiver.metrics().increment(TOKENS_PASSED, &[]);
             // log::info!("Passed token to next alive thinker {}", next_thinker.id)
         } else {
             log::error!("All following thinkers are currently timed out. Dropping token.");
@@ -276,6 +407,14 @@ impl Thinker {
         }
     }
 
+    /// Every replica of a fork gets the message, only the current primary answers
+    fn send_to_forks(&self, message: impl Fn() -> ForkMessages) {
+        self.forks
+            .iter()
+            .flat_map(|fork| fork.replicas())
+            .for_each(|address| self.transceiver.send(message(), address));
+    }
+
     /// returns true if passed token is still uptodate
     fn mark_token_as_seen(&mut self, token_ref: &TokenRef) -> bool {
         let last_seen = self
@@ -302,6 +441,19 @@ impl Thinker {
             ThinkerMessage::Init { .. } => {
                 log::error!("Already initialized but got init message from {entity}");
             }
+            ThinkerMessage::Control(message) => {
+                self.control.handle_message(message, &mut self.transceiver);
+            }
+            ThinkerMessage::Subscribe { kinds } => {
+                log::info!("Visualizer {entity} subscribed");
+                self.subscribers.subscribe(
+                    &mut self.visualizers,
+                    VisualizerRef {
+                        address: entity,
+                        kinds,
+                    },
+                );
+            }
             ThinkerMessage::Token(token) => {
                 match &mut self.state {
                     ThinkerState::Thinking { .. }
@@ -311,7 +463,10 @@ impl Thinker {
                         if self.mark_token_as_seen(&TokenRef::from(&token)) {
                             self.pass_token(token);
                         } else {
-                            log::warn!("Dropping outdated token {:?}", token);
+                            self.events.emit(Event::OutdatedTokenDropped {
+                                token: token.id,
+                                version: token.version,
+                            });
                         }
                     }
                     ThinkerState::Hungry { token_state } => match token_state {
@@ -323,7 +478,10 @@ impl Thinker {
                             if self.mark_token_as_seen(&TokenRef::from(&token)) {
                                 self.pass_token(token);
                             } else {
-                                log::warn!("Dropping outdated token {:?}", token);
+                                self.events.emit(Event::OutdatedTokenDropped {
+                                    token: token.id,
+                                    version: token.version,
+                                });
                             }
                         }
                     },
@@ -347,11 +505,21 @@ impl Thinker {
                             match new_fork_state {
                                 ForkState::Taken => {
                                     if matches!(own_fork_state.state, ForkState::Queued) {
-                                        log::info!("Taken fork {}", fork_id);
                                         own_fork_state.state = ForkState::Taken;
+                                        self.events.emit(Event::ForkTaken {
+                                            fork: fork_id.clone(),
+                                        });
+                                    }
+                                }
+                                // A primary that lost its lease no longer confirms the grant
+                                ForkState::Queued => {
+                                    if matches!(own_fork_state.state, ForkState::Taken) {
+                                        own_fork_state.state = ForkState::Queued;
+                                        self.events.emit(Event::ForkUnconfirmed {
+                                            fork: fork_id.clone(),
+                                        });
                                     }
                                 }
-                                ForkState::Queued => (),
                             }
                             own_fork_state.last_seen_at = Instant::now()
                         } else {
@@ -366,8 +534,11 @@ impl Thinker {
                             .zip(&self.forks)
                             .find(|(_, fork)| fork.id.eq(&fork_id))
                         {
+                            // Only a confirmed grant keeps the meal going
                             Some((last, _)) => {
-                                *last = Instant::now();
+                                if matches!(new_fork_state, ForkState::Taken) {
+                                    *last = Instant::now();
+                                }
                             }
                             None => {
                                 log::warn!("Got fork keep alive from unkown fork {}", fork_id)
@@ -376,6 +547,31 @@ impl Thinker {
                     }
                 };
             }
+            ThinkerMessage::Leaving(id) => {
+                if let Some(next_thinker) = self
+                    .next_thinkers
+                    .iter_mut()
+                    .find(|next_thinker| next_thinker.thinker.id.eq(&id))
+                {
+                    next_thinker.mark_left();
+                }
+                self.events.emit(Event::ThinkerLeft { thinker: id });
+            }
+            ThinkerMessage::ForkLeaving(fork_id) => {
+                if !self.forks.iter().any(|fork| fork.id.eq(&fork_id)) {
+                    log::warn!("Got leave from unkown fork {}", fork_id);
+                } else if let ThinkerState::WaitingForForks { token, .. }
+                | ThinkerState::Eating { token, .. } = &self.state
+                {
+                    let token = token.clone();
+                    self.send_to_forks(|| ForkMessages::Release(self.id.clone()));
+                    self.pass_token(token);
+                    self.state = ThinkerState::Hungry {
+                        token_state: HungryTokenState::WaitingForToken,
+                    };
+                    self.events.emit(Event::ForkLeft { fork: fork_id });
+                }
+            }
             ThinkerMessage::ThinkerAliveRequest(_) => {
                 self.transceiver.send(
                     ThinkerMessage::ThinkerAliveResponse(self.id.clone()),
@@ -405,11 +601,10 @@ impl Thinker {
                         .unwrap()
                     {
                         TokenPriority::Low | TokenPriority::Equal => {
-                            log::warn!(
-                                "Outdated proposal {} for token {}",
-                                proposal.propose_version,
-                                last_seen_token.current_token_ref.id,
-                            );
+                            self.events.emit(Event::OutdatedProposal {
+                                token: proposal.proposed_token.id.clone(),
+                                propose_version: proposal.propose_version,
+                            });
                             // Proposal outdated, do nothing
                         }
                         TokenPriority::High => match &last_seen_token.state {
@@ -433,20 +628,18 @@ impl Thinker {
                                             last_seen_token.last_seen_at = Instant::now();
                                             last_seen_token.current_proposal_version += 1;
                                             last_seen_token.state = TokenRefLastSeenState::Passive;
-                                            log::info!(
-                                                "Got proposal from more priority issuer {} for token {}. Stepping down",
-                                                proposal.proposed_token.issuer,
-                                                proposal.proposed_token.id,
-                                            );
+                                            self.events.emit(Event::ProposalSteppedDown {
+                                                token: proposal.proposed_token.id.clone(),
+                                                issuer: proposal.proposed_token.issuer.clone(),
+                                            });
                                             self.pass_token_proposal(proposal);
                                         }
                                         TokenPriority::Equal => unreachable!(),
                                         TokenPriority::Low => {
-                                            log::info!(
-                                                "Got proposal from lower priority issuer {} for token {}. Dropping proposal",
-                                                proposal.proposed_token.issuer,
-                                                proposal.proposed_token.id,
-                                            );
+                                            self.events.emit(Event::ProposalDropped {
+                                                token: proposal.proposed_token.id.clone(),
+                                                issuer: proposal.proposed_token.issuer.clone(),
+                                            });
                                         }
                                     }
                                 } else if proposal
@@ -462,19 +655,23 @@ impl Thinker {
                                         last_seen_at: Instant::now(),
                                         state: TokenRefLastSeenState::Passive,
                                     };
-                                    log::info!("Generated new token {}", token.id);
+                                    self.events.emit(Event::TokenGenerated {
+                                        token: token.id.clone(),
+                                    });
                                     self.pass_token(token);
                                 } else {
-                                    log::warn!(
-                                        "Dropping own outdated token proposal {:?}",
-                                        proposal
-                                    )
+                                    self.events.emit(Event::OwnProposalOutdated {
+                                        token: proposal.proposed_token.id,
+                                        propose_version: proposal.propose_version,
+                                    })
                                 }
                             }
                         },
                     }
                 } else {
-                    log::warn!("Token proposal for unkown token {:?}", proposal);
+                    self.events.emit(Event::UnknownTokenProposal {
+                        token: proposal.proposed_token.id,
+                    });
                 }
             }
             ThinkerMessage::TokenAliveBroadcast {
@@ -488,7 +685,8 @@ impl Thinker {
         }
     }
 
-    pub fn update_state(&mut self) {
+    /// Alive requests to the next thinkers and running token proposals
+    fn send_heartbeat(&self) {
         let mut alive_amount = 0;
         for next_thinker in self.next_thinkers.iter() {
             self.transceiver.send(
@@ -503,29 +701,37 @@ impl Thinker {
             }
         }
 
+        self.available_tokens
+            .iter()
+            .filter_map(|last_seen| match &last_seen.state {
+                TokenRefLastSeenState::Passive => None,
+                TokenRefLastSeenState::Propose(token_proposal) => Some(token_proposal),
+            })
+            .for_each(|proposal| self.pass_token_proposal(proposal.clone()));
+    }
+
+    /// Transitions are taken on every call, periodic messages only once per heartbeat
+    pub fn update_state(&mut self) {
+        self.beat = self.heartbeat.beat();
         self.available_tokens.iter_mut().for_each(|last_seen| {
             if matches!(last_seen.state, TokenRefLastSeenState::Passive) && last_seen.is_timed_out()
             {
                 last_seen.current_proposal_version += 1;
+                self.transceiver.metrics().increment(PROPOSALS_STARTED, &[]);
                 last_seen.state = TokenRefLastSeenState::Propose(
                     last_seen
                         .current_token_ref
                         .generate_proposal(self.id.clone(), last_seen.current_proposal_version),
                 );
-                log::info!(
-                    "Token {} timed out, switching in proposed state",
-                    last_seen.current_token_ref.id
-                );
+                self.events.emit(Event::TokenTimedOut {
+                    token: last_seen.current_token_ref.id.clone(),
+                    propose_version: last_seen.current_proposal_version,
+                });
             }
         });
-
-        self.available_tokens
-            .iter()
-            .filter_map(|last_seen| match &last_seen.state {
-                TokenRefLastSeenState::Passive => None,
-                TokenRefLastSeenState::Propose(token_proposal) => Some(token_proposal),
-            })
-            .for_each(|proposal| self.pass_token_proposal(proposal.clone()));
+        if self.beat {
+            self.send_heartbeat();
+        }
 
         let active_token = match &self.state {
             ThinkerState::WaitingForForks { token, .. } | ThinkerState::Eating { token, .. } => {
@@ -545,7 +751,10 @@ impl Thinker {
             if !token_still_valid {
                 self.state = ThinkerState::Hungry {
                     token_state: HungryTokenState::WaitingForToken,
-                }
+                };
+                self.events.emit(Event::TokenInvalidated {
+                    token: active_token.id,
+                });
             }
         }
 
@@ -553,7 +762,7 @@ impl Thinker {
             ThinkerState::Thinking { stop_thinking_at } => {
                 match Instant::now().cmp(stop_thinking_at) {
                     std::cmp::Ordering::Equal | std::cmp::Ordering::Greater => {
-                        log::info!("Got hungry");
+                        self.events.emit(Event::GotHungry);
                         self.state = ThinkerState::Hungry {
                             token_state: HungryTokenState::WaitingForToken,
                         };
@@ -570,9 +779,10 @@ impl Thinker {
                     }
                     HungryTokenState::TokenReceived(token) => {
                         self.token_broadcast(token.into(), self.id.clone());
-                        self.forks.iter().for_each(|fork| {
-                            self.transceiver
-                                .send(ForkMessages::KeepAlive(self.id.clone()), &fork.address);
+                        self.send_to_forks(|| ForkMessages::KeepAlive(self.id.clone()));
+                        self.events.emit(Event::GotToken {
+                            token: token.id.clone(),
+                            version: token.version,
                         });
                         self.state = ThinkerState::WaitingForForks {
                             waiting_state: self.forks.clone().map(|_| WaitingForForkState {
@@ -581,7 +791,6 @@ impl Thinker {
                             }),
                             token: token.clone(),
                         };
-                        log::info!("Got token, requesting forks");
                     }
                 }
                 // Nothing to do here
@@ -594,95 +803,286 @@ impl Thinker {
                     waiting_fork_state.last_seen_at.elapsed() > KEEP_ALIVE_TIMEOUT
                 });
                 if expired {
-                    self.forks.iter().for_each(|fork| {
-                        self.transceiver
-                            .send(ForkMessages::Release(self.id.clone()), &fork.address);
-                    });
+                    self.send_to_forks(|| ForkMessages::Release(self.id.clone()));
                     self.pass_token(token.clone());
                     self.state = ThinkerState::Hungry {
                         token_state: HungryTokenState::WaitingForToken,
-                    }
-                } else {
-                    self.forks.iter().for_each(|fork| {
-                        self.transceiver
-                            .send(ForkMessages::KeepAlive(self.id.clone()), &fork.address);
+                    };
+                    self.events.emit(Event::ForksTimedOut {
+                        while_eating: false,
                     });
-                    self.token_broadcast(token.into(), self.id.clone());
+                } else {
+                    if self.beat {
+                        self.send_to_forks(|| ForkMessages::KeepAlive(self.id.clone()));
+                        self.token_broadcast(token.into(), self.id.clone());
+                    }
                     let all_taken = waiting_state
                         .iter()
                         .all(|el| matches!(el.state, ForkState::Taken));
 
                     if all_taken {
+                        self.events.emit(Event::StartedEating {
+                            token: token.id.clone(),
+                        });
                         self.state = ThinkerState::Eating {
+                            started_eating_at: Instant::now(),
                             stop_eating_at: Instant::now()
-                                + self.rng.random_range(MIN_EATING_TIME..=MAX_EATING_TIME),
+                                + self.rng.random_range(self.timing.eating.clone()),
                             fork_last_seen_at: waiting_state
                                 .clone()
                                 .map(|waiting_state| waiting_state.last_seen_at),
                             token: token.clone(),
                         };
-                        log::info!("Start eating");
                     }
                 }
             }
             ThinkerState::Eating {
+                started_eating_at,
                 stop_eating_at,
                 fork_last_seen_at,
                 token,
             } => match Instant::now().cmp(stop_eating_at) {
                 std::cmp::Ordering::Equal | std::cmp::Ordering::Greater => {
+                    self.meal_statistics.meals += 1;
+                    self.transceiver.metrics().increment(MEALS, &[]);
+                    self.meal_statistics.eating_time += started_eating_at.elapsed();
+                    let eating_time_ms = started_eating_at.elapsed().as_millis() as u64;
                     self.pass_token(token.clone());
-                    self.forks.iter().for_each(|fork| {
-                        self.transceiver
-                            .send(ForkMessages::Release(self.id.clone()), &fork.address)
-                    });
+                    self.send_to_forks(|| ForkMessages::Release(self.id.clone()));
                     self.state = ThinkerState::Thinking {
                         stop_thinking_at: Instant::now()
-                            + self.rng.random_range(MIN_THINKING_TIME..=MAX_THINKING_TIME),
+                            + self.rng.random_range(self.timing.thinking.clone()),
                     };
-                    log::info!("Start Thinking, release forks");
+                    self.events.emit(Event::FinishedEating { eating_time_ms });
                 }
                 std::cmp::Ordering::Less => {
-                    self.forks.iter().for_each(|fork| {
-                        self.transceiver
-                            .send(ForkMessages::KeepAlive(self.id.clone()), &fork.address);
-                    });
+                    if self.beat {
+                        self.send_to_forks(|| ForkMessages::KeepAlive(self.id.clone()));
+                    }
+                    // The neighbour may get a fork that stopped confirming, so one is enough
                     let expired = fork_last_seen_at
                         .iter()
-                        .all(|at| at.elapsed() > KEEP_ALIVE_TIMEOUT);
+                        .any(|at| at.elapsed() > KEEP_ALIVE_TIMEOUT);
                     if expired {
                         self.pass_token(token.clone());
-                        self.forks.iter().for_each(|fork| {
-                            self.transceiver
-                                .send(ForkMessages::Release(self.id.clone()), &fork.address)
-                        });
+                        self.send_to_forks(|| ForkMessages::Release(self.id.clone()));
                         self.state = ThinkerState::Hungry {
                             token_state: HungryTokenState::WaitingForToken,
                         };
-                    } else {
+                        self.events
+                            .emit(Event::ForksTimedOut { while_eating: true });
+                    } else if self.beat {
                         self.token_broadcast(token.into(), self.id.clone());
                     }
                 }
             },
         }
+        self.save_checkpoint();
+    }
+}
+
+impl Node for Thinker {
+    type Message = ThinkerMessage;
+    type Init = InitThinkerParams;
+    type Config = ThinkerConfig;
+    type Checkpoint = ThinkerCheckpoint;
+
+    fn init_request(id: Id<Self>) -> InitMessages {
+        InitMessages::ThinkerRequest(id)
+    }
+
+    fn take_init(message: ThinkerMessage) -> Result<InitThinkerParams, ThinkerMessage> {
+        match message {
+            ThinkerMessage::Init(init_params) => Ok(*init_params),
+            message => Err(message),
+        }
+    }
+
+    /// The init server assigns the id, so the requested one is not used
+    fn config(_id: Id<Self>, address: SocketAddr, init: &InitThinkerParams) -> ThinkerConfig {
+        ThinkerConfig {
+            id: init.id.clone(),
+            visualizers: init.visualizers.clone(),
+            address,
+            forks: init.forks.clone(),
+            next_thinkers: init.next_thinkers.clone(),
+            previous_thinkers: init.previous_thinkers.clone(),
+            token: init.token.clone(),
+            available_tokens: init.available_tokens.clone(),
+            seed: init.seed,
+        }
+    }
+
+    fn from_config(config: ThinkerConfig) -> (Id<Self>, InitThinkerParams) {
+        let init = InitThinkerParams {
+            id: config.id.clone(),
+            token: config.token,
+            forks: config.forks,
+            next_thinkers: config.next_thinkers,
+            previous_thinkers: config.previous_thinkers,
+            visualizers: config.visualizers,
+            available_tokens: config.available_tokens,
+            seed: config.seed,
+        };
+        (config.id, init)
+    }
+
+    fn config_address(config: &ThinkerConfig) -> SocketAddr {
+        config.address
+    }
+
+    fn file_name(config: &ThinkerConfig) -> String {
+        format!("thinker_{}", config.id.value)
+    }
+
+    fn build(_id: Id<Self>, init: InitThinkerParams, setup: NodeSetup<Self>) -> Self {
+        // A checkpoint means the thinker ran before, its token was probably regenerated already
+        let token = init.token.filter(|_| setup.checkpoint.is_none());
+        Self::new(ThinkerInitParams {
+            id: init.id,
+            transceiver: setup.transceiver,
+            unhandled_messages: setup.unhandled_messages,
+            forks: init.forks,
+            next_thinkers: init.next_thinkers,
+            previous_thinkers: init.previous_thinkers,
+            token,
+            available_tokens: init.available_tokens,
+            visualizers: init.visualizers,
+            checkpoint: setup.checkpoint,
+            checkpoint_file: setup.checkpoint_file,
+            control: setup.control,
+            event_log: setup.event_log,
+            timing: MealTiming::default(),
+            seed: init.seed,
+        })
+    }
+
+    fn id(&self) -> &Id<Self> {
+        &self.id
+    }
+
+    fn print_started(&mut self) {
+        self.events.emit(Event::ThinkerStarted {
+            address: self.transceiver.local_address(),
+        });
     }
 
-    pub fn tick(&mut self, buffer: &mut [u8]) {
+    fn tick(&mut self, buffer: &mut [u8]) {
         while let Some((message, entity)) = self.transceiver.receive::<ThinkerMessage>(buffer) {
+            if self.control.is_paused() && !matches!(message, ThinkerMessage::Control(_)) {
+                continue;
+            }
             self.handle_message(message, entity);
         }
-        self.update_state();
+        match self.control.is_paused() {
+            // Only the heartbeat moves on, otherwise the past deadline wakes the node right away
+            true => self.beat = self.heartbeat.beat(),
+            false => self.update_state(),
+        }
+    }
+
+    /// Sends the state on every heartbeat and whenever it changed
+    fn update_visualizer(&mut self) {
+        self.subscribers.expire();
+        if self.control.is_paused() || self.visualizers.is_empty() && self.subscribers.is_empty() {
+            return;
+        }
+        let state = VisualizerThinkerState::from(&self.state);
+        if !self.beat && self.visualized.as_ref() == Some(&state) {
+            return;
+        }
+        self.visualized = Some(state.clone());
+        visualizer::notify(
+            self.visualizers.iter().chain(self.subscribers.iter()),
+            &self.transceiver,
+            VisualizerMessages::ThinkerStateChanged {
+                id: self.id.clone(),
+                forks: self.forks.clone(),
+                state,
+                token_state: self.available_tokens.iter().map(|el| el.into()).collect(),
+            },
+        );
+    }
+
+    /// Next heartbeat, end of thinking or eating or delayed message, whichever comes first
+    fn next_deadline(&self) -> Instant {
+        let timer = match &self.state {
+            // The timers run out while paused and are handled once resumed
+            _ if self.control.is_paused() => None,
+            ThinkerState::Thinking { stop_thinking_at } => Some(*stop_thinking_at),
+            ThinkerState::Eating { stop_eating_at, .. } => Some(*stop_eating_at),
+            ThinkerState::Hungry { .. } | ThinkerState::WaitingForForks { .. } => None,
+        };
+        [timer, self.transceiver.next_delivery()]
+            .into_iter()
+            .flatten()
+            .fold(self.heartbeat.next_at(), Instant::min)
+    }
+
+    fn wait(&mut self, loop_mode: LoopMode) {
+        let deadline = self.next_deadline();
+        loop_mode.wait(&mut self.transceiver, deadline);
+    }
+
+    fn should_crash(&mut self) -> CrashStatus {
+        let crash_status = self.control.should_crash();
+        self.transceiver.metrics().count_crash(&crash_status);
+        crash_status
+    }
+
+    fn reset(mut self) -> Self {
+        let checkpoint = self.checkpoint();
+        let seed = self.seeded.then(|| self.rng.random());
+        Self::new(ThinkerInitParams {
+            id: self.id,
+            transceiver: self.transceiver.reset(),
+            unhandled_messages: vec![],
+            forks: self.forks,
+            next_thinkers: self
+                .next_thinkers
+                .into_iter()
+                .map(|el| el.thinker)
+                .collect(),
+            previous_thinkers: self.previous_thinkers,
+            token: None,
+            available_tokens: self
+                .available_tokens
+                .into_iter()
+                .map(|el| el.current_token_ref)
+                .collect(),
+            visualizers: self.visualizers,
+            checkpoint: Some(checkpoint),
+            checkpoint_file: self.checkpoint_file,
+            control: self.control,
+            event_log: self.events,
+            timing: self.timing,
+            seed,
+        })
     }
 
-    pub fn update_visualizer(&self) {
-        if let Some(visualizer) = &self.visualizer {
+    /// Hands on the token and releases the forks, so no neighbour waits for a timeout
+    fn shutdown(&mut self) {
+        self.events.emit(Event::ShuttingDown);
+        let state = std::mem::replace(
+            &mut self.state,
+            ThinkerState::Hungry {
+                token_state: HungryTokenState::WaitingForToken,
+            },
+        );
+        match state {
+            ThinkerState::WaitingForForks { token, .. } | ThinkerState::Eating { token, .. } => {
+                self.send_to_forks(|| ForkMessages::Release(self.id.clone()));
+                self.pass_token(token);
+            }
+            ThinkerState::Hungry {
+                token_state: HungryTokenState::TokenReceived(token),
+            } => self.pass_token(token),
+            ThinkerState::Thinking { .. } | ThinkerState::Hungry { .. } => (),
+        }
+        for previous_thinker in &self.previous_thinkers {
             self.transceiver.send(
-                VisualizerMessages::ThinkerStateChanged {
-                    id: self.id.clone(),
-                    state: (&self.state).into(),
-                    token_state: self.available_tokens.iter().map(|el| el.into()).collect(),
-                },
-                &visualizer.address,
+                ThinkerMessage::Leaving(self.id.clone()),
+                &previous_thinker.address,
             );
         }
     }
@@ -0,0 +1,218 @@
+use std::net::SocketAddr;
+use std::path::Path;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::lib::fork::ForkRef;
+use crate::lib::messages::VisualizerMessages;
+use crate::lib::messages::fork_messages::InitForkParams;
+use crate::lib::messages::thinker_messages::{InitThinkerParams, Token};
+use crate::lib::thinker::ThinkerRef;
+use crate::lib::utils::Id;
+use crate::lib::visualizer::VisualizerRef;
+
+/// Written next to the configs by `init generate`, together with a `.dot` of the same name
+pub const MANIFEST_FILE: &str = "topology.json";
+
+/// Wiring of the ring, thinker `i` sits between fork `i` and fork `i + 1`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Topology {
+    pub thinkers: Vec<ThinkerRef>,
+    pub forks: Vec<ForkRef>,
+    pub tokens: Vec<Token>,
+    pub visualizers: Vec<VisualizerRef>,
+    pub next_thinkers_amount: usize,
+    /// Passed on to every node, see `with_seed`
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+impl Topology {
+    /// Nodes are wired in the given order, `fork_replicas` consecutive forks form one fork whose
+    /// first replica starts as primary. The first `tokens` thinkers issue a token each.
+    pub fn ring(
+        thinkers: Vec<ThinkerRef>,
+        forks: Vec<ForkRef>,
+        tokens: usize,
+        visualizers: Vec<VisualizerRef>,
+        next_thinkers_amount: usize,
+        fork_replicas: usize,
+    ) -> Self {
+        let forks = forks
+            .chunks(fork_replicas)
+            .map(|replicas| ForkRef {
+                address: replicas[0].address,
+                id: replicas[0].id.clone(),
+                backups: replicas[1..].iter().map(|fork| fork.address).collect(),
+            })
+            .collect();
+        let tokens = thinkers
+            .iter()
+            .take(tokens)
+            .map(|thinker| Token::create(thinker.id.clone()))
+            .collect();
+        Self {
+            thinkers,
+            forks,
+            tokens,
+            visualizers,
+            next_thinkers_amount,
+            seed: None,
+        }
+    }
+
+    /// Draws all node and token ids from `seed` and hands every node a seed of its own, so the
+    /// same seed gives the same ring, meal timing and random crashes
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        for thinker in &mut self.thinkers {
+            thinker.id = Id::from_rng(&mut rng);
+        }
+        for fork in &mut self.forks {
+            fork.id = Id::from_rng(&mut rng);
+        }
+        for (token, thinker) in self.tokens.iter_mut().zip(&self.thinkers) {
+            token.id = Id::from_rng(&mut rng);
+            token.issuer = thinker.id.clone();
+        }
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Seed of the node with this id, replicas of a fork share the id and differ in rank
+    fn node_seed(&self, id: &uuid::Uuid, rank: usize) -> Option<u64> {
+        self.seed
+            .map(|seed| StdRng::seed_from_u64(seed ^ id.as_u64_pair().0 ^ rank as u64).random())
+    }
+
+    pub fn thinker_init(&self, index: usize) -> InitThinkerParams {
+        let thinker = &self.thinkers[index];
+        InitThinkerParams {
+            id: thinker.id.clone(),
+            token: self
+                .tokens
+                .iter()
+                .find(|token| token.issuer.eq(&thinker.id))
+                .cloned(),
+            forks: [0, 1].map(|offset| self.forks[(index + offset) % self.forks.len()].clone()),
+            next_thinkers: (1..=self.next_thinkers_amount)
+                .map(|offset| self.thinkers[(index + offset) % self.thinkers.len()].clone())
+                .collect(),
+            previous_thinkers: (1..=self.next_thinkers_amount)
+                .map(|offset| {
+                    let len = self.thinkers.len();
+                    self.thinkers[(index + len - offset % len) % len].clone()
+                })
+                .collect(),
+            visualizers: self.visualizers.clone(),
+            available_tokens: self.tokens.iter().map(|token| token.into()).collect(),
+            seed: self.node_seed(&thinker.id.value, 0),
+        }
+    }
+
+    /// Init params of every fork replica together with its address
+    pub fn fork_inits(&self) -> Vec<(SocketAddr, InitForkParams)> {
+        self.forks
+            .iter()
+            .flat_map(|fork| {
+                let replicas = fork.replicas().copied().collect::<Vec<_>>();
+                replicas
+                    .iter()
+                    .enumerate()
+                    .map(|(rank, address)| {
+                        let init = InitForkParams {
+                            id: fork.id.clone(),
+                            visualizers: self.visualizers.clone(),
+                            replicas: replicas.clone(),
+                            rank,
+                            seed: self.node_seed(&fork.id.value, rank),
+                        };
+                        (*address, init)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    pub fn visualizer_init(&self) -> VisualizerMessages {
+        VisualizerMessages::Init {
+            thinkers: self.thinkers.clone(),
+            forks: self.forks.clone(),
+        }
+    }
+
+    /// Writes the ring as JSON to `path` and as Graphviz DOT next to it
+    pub fn export(&self, path: &Path) -> Result<(), String> {
+        let write = |path: &Path, contents: String| {
+            std::fs::write(path, contents)
+                .map_err(|e| format!("Could not write {}: {e}", path.display()))
+        };
+        write(path, serde_json::to_string_pretty(self).unwrap() + "\n")?;
+        write(&path.with_extension("dot"), self.to_dot())
+    }
+
+    /// Reads a topology written with `export`
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+        serde_json::from_str(&source)
+            .map_err(|e| format!("Invalid topology {}: {e}", path.display()))
+    }
+
+    /// Thinkers with the forks they share and dashed edges to their successors, initial token
+    /// holders are filled
+    pub fn to_dot(&self) -> String {
+        let short = |id: &uuid::Uuid| id.to_string()[..8].to_string();
+        let mut dot = String::from("digraph ring {\n    node [fontname=\"monospace\"];\n");
+        for (index, thinker) in self.thinkers.iter().enumerate() {
+            let token = self
+                .tokens
+                .iter()
+                .find(|token| token.issuer.eq(&thinker.id))
+                .map(|token| format!("\\ntoken {}", short(&token.id.value)))
+                .unwrap_or_default();
+            let style = match token.is_empty() {
+                true => "",
+                false => ", style=filled, fillcolor=gold",
+            };
+            dot += &format!(
+                "    \"{}\" [label=\"thinker {index}\\n{}\\n{}{token}\"{style}];\n",
+                thinker.id.value,
+                short(&thinker.id.value),
+                thinker.address
+            );
+        }
+        for (index, fork) in self.forks.iter().enumerate() {
+            let backups = fork
+                .backups
+                .iter()
+                .map(|address| format!("\\nbackup {address}"))
+                .collect::<String>();
+            dot += &format!(
+                "    \"{}\" [label=\"fork {index}\\n{}\\n{}{backups}\", shape=box];\n",
+                fork.id.value,
+                short(&fork.id.value),
+                fork.address
+            );
+        }
+        for (index, thinker) in self.thinkers.iter().enumerate() {
+            for offset in [0, 1] {
+                let fork = &self.forks[(index + offset) % self.forks.len()];
+                dot += &format!(
+                    "    \"{}\" -> \"{}\" [dir=none];\n",
+                    thinker.id.value, fork.id.value
+                );
+            }
+            for offset in 1..=self.next_thinkers_amount {
+                let next = &self.thinkers[(index + offset) % self.thinkers.len()];
+                dot += &format!(
+                    "    \"{}\" -> \"{}\" [style=dashed, color=gray];\n",
+                    thinker.id.value, next.id.value
+                );
+            }
+        }
+        dot + "}\n"
+    }
+}
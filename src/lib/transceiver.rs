@@ -1,6 +1,6 @@
 use std::net::{SocketAddr, UdpSocket};
+use std::time::Instant;
 
-use rand::Rng;
 use rkyv::api::high::{HighSerializer, HighValidator};
 use rkyv::de::Pool;
 use rkyv::rancor::Strategy;
@@ -8,67 +8,194 @@ use rkyv::ser::allocator::ArenaHandle;
 use rkyv::util::AlignedVec;
 use rkyv::{Archive, Deserialize, Serialize, bytecheck::CheckBytes};
 
-use crate::KEEP_MESSAGE_PERCENTAGE;
+use crate::NETWORK_BUFFER_SIZE;
+use crate::lib::clock::{SharedClock, VectorClock};
+use crate::lib::faults::{FaultConfig, FaultLayer, Partition};
+use crate::lib::messages::MessageKind;
+use crate::lib::metrics::{MESSAGES_DROPPED, MESSAGES_RECEIVED, MESSAGES_SENT, Metrics};
+use crate::lib::transport::{Transport, UdpTransport};
+
+/// Wire format of every message, the clock is only set if the sender keeps one
+#[derive(Archive, Serialize, Deserialize, Debug)]
+struct Envelope<T> {
+    clock: Option<VectorClock>,
+    message: T,
+}
 
 #[derive(Debug)]
 pub struct Transceiver {
-    socket: UdpSocket,
+    transport: Box<dyn Transport>,
+    faults: FaultLayer,
+    metrics: Metrics,
+    clock: Option<SharedClock>,
 }
 impl Transceiver {
     pub fn new(socket: UdpSocket) -> Self {
-        socket.set_nonblocking(true).unwrap();
-        Self { socket }
+        Self::with_transport(Box::new(UdpTransport::new(socket)))
+    }
+
+    pub fn with_transport(transport: Box<dyn Transport>) -> Self {
+        Self {
+            transport,
+            faults: FaultLayer::new(FaultConfig::default()),
+            metrics: Metrics::default(),
+            clock: None,
+        }
     }
 
     pub fn reset(self) -> Self {
-        let address = self.socket.local_addr().unwrap();
-        std::mem::drop(self);
-        let socket = UdpSocket::bind(address).unwrap();
-        socket.set_nonblocking(true).unwrap();
-        Self { socket }
+        Self {
+            transport: self.transport.reset(),
+            faults: self.faults.reset(),
+            metrics: self.metrics,
+            clock: self.clock,
+        }
+    }
+
+    /// Stamps every sent message with a vector clock and merges the clocks of received ones
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = Some(clock);
+    }
+
+    pub fn clock(&self) -> Option<&SharedClock> {
+        self.clock.as_ref()
+    }
+
+    /// Counters of this node, kept across resets
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn set_fault_config(&mut self, config: FaultConfig) {
+        self.faults = FaultLayer::new(config);
+    }
+
+    pub fn set_drop_percentage(&mut self, drop_percentage: f64) {
+        self.faults.config.drop_percentage = drop_percentage.clamp(0.0, 1.0);
+    }
+
+    /// Cuts the links of this node to the blocked addresses
+    pub fn partition(&mut self, name: String, blocked: Vec<SocketAddr>) {
+        self.faults.partition(Partition {
+            name,
+            sides: [vec![self.local_address()], blocked],
+        });
+    }
+
+    pub fn heal(&mut self, name: Option<String>) {
+        self.faults.heal(name);
     }
 
     pub fn send_reliable<T>(&self, message: T, to: &SocketAddr)
     where
         T: for<'a> Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, rkyv::rancor::Error>>
             + Archive
+            + MessageKind
             + std::fmt::Debug,
         T::Archived: for<'a> CheckBytes<HighValidator<'a, rkyv::rancor::Error>>
             + Deserialize<T, Strategy<Pool, rkyv::rancor::Error>>,
     {
-        let message_bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&message).unwrap();
-        self.socket.send_to(&message_bytes, to).unwrap();
+        if self.faults.is_partitioned(&self.local_address(), to) {
+            self.metrics.increment(
+                MESSAGES_DROPPED,
+                &[("type", message.kind()), ("reason", "partition")],
+            );
+            return;
+        }
+        self.metrics
+            .increment(MESSAGES_SENT, &[("type", message.kind())]);
+        let envelope = Envelope {
+            clock: self.clock.as_ref().map(SharedClock::tick),
+            message,
+        };
+        let message_bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&envelope).unwrap();
+        if message_bytes.len() > NETWORK_BUFFER_SIZE {
+            panic!(
+                "{} message to {to} is {} bytes, more than the {NETWORK_BUFFER_SIZE} bytes a datagram can carry",
+                envelope.message.kind(),
+                message_bytes.len()
+            );
+        }
+        self.transport.send_to(&message_bytes, to);
     }
 
     pub fn send<T>(&self, message: T, to: &SocketAddr)
     where
         T: for<'a> Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, rkyv::rancor::Error>>
             + Archive
+            + MessageKind
             + std::fmt::Debug,
         T::Archived: for<'a> CheckBytes<HighValidator<'a, rkyv::rancor::Error>>
             + Deserialize<T, Strategy<Pool, rkyv::rancor::Error>>,
     {
-        if rand::rng().random_bool(KEEP_MESSAGE_PERCENTAGE) {
-            self.send_reliable(message, to);
+        if self.faults.is_lost() {
+            self.metrics.increment(
+                MESSAGES_DROPPED,
+                &[("type", message.kind()), ("reason", "loss")],
+            );
+            return;
         }
+        self.send_reliable(message, to);
     }
 
-    pub fn receive<T>(&self, buffer: &mut [u8]) -> Option<(T, SocketAddr)>
+    pub fn receive<T>(&mut self, buffer: &mut [u8]) -> Option<(T, SocketAddr)>
     where
-        T: Archive + std::fmt::Debug,
+        T: Archive + MessageKind + std::fmt::Debug,
         T::Archived: for<'a> CheckBytes<HighValidator<'a, rkyv::rancor::Error>>
             + Deserialize<T, Strategy<Pool, rkyv::rancor::Error>>,
     {
-        let (len, entity) = match self.socket.recv_from(buffer) {
-            Ok(bytes) => bytes,
-            Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock) => None?,
-            Err(e) => panic!("{:?}", e),
+        let local_address = self.local_address();
+        while let Some((len, entity)) = self.transport.recv_from(buffer) {
+            if !self
+                .faults
+                .incoming(&buffer[0..len], &local_address, entity)
+            {
+                // Not decoded yet, so the type is unknown
+                self.metrics.increment(
+                    MESSAGES_DROPPED,
+                    &[("type", "unknown"), ("reason", "partition")],
+                );
+            }
+        }
+        // Stale datagrams of a previous run on the same port may not decode, they are skipped
+        let (Envelope { clock, message }, entity) = loop {
+            let (bytes, entity) = self.faults.deliver()?;
+            // Archived messages have to be aligned, the caller's buffer may not be
+            let mut aligned = AlignedVec::<16>::with_capacity(bytes.len());
+            aligned.extend_from_slice(&bytes);
+            match rkyv::from_bytes::<Envelope<T>, rkyv::rancor::Error>(&aligned) {
+                Ok(envelope) => break (envelope, entity),
+                Err(e) => {
+                    log::warn!("Dropping undecodable message from {entity}: {e}");
+                    self.metrics.increment(
+                        MESSAGES_DROPPED,
+                        &[("type", "unknown"), ("reason", "undecodable")],
+                    );
+                }
+            }
         };
-        let message = rkyv::from_bytes::<T, rkyv::rancor::Error>(&buffer[0..len]).unwrap();
+        if let (Some(own_clock), Some(clock)) = (&self.clock, clock) {
+            own_clock.receive(&clock);
+        }
+        self.metrics
+            .increment(MESSAGES_RECEIVED, &[("type", message.kind())]);
         Some((message, entity))
     }
 
+    /// When the next message held back by the simulated latency is due
+    pub fn next_delivery(&self) -> Option<Instant> {
+        self.faults.next_delivery()
+    }
+
+    /// Blocks until a datagram arrives or the deadline passed
+    pub fn wait(&mut self, deadline: Instant) {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        if !timeout.is_zero() {
+            self.transport.wait(timeout);
+        }
+    }
+
     pub fn local_address(&self) -> SocketAddr {
-        self.socket.local_addr().unwrap()
+        self.transport.local_address()
     }
 }
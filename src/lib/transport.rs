@@ -0,0 +1,155 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+/// Datagram transport underneath a `Transceiver`
+pub trait Transport: std::fmt::Debug + Send {
+    fn send_to(&self, bytes: &[u8], to: &SocketAddr);
+    /// Non blocking, returns `None` if nothing is waiting
+    fn recv_from(&self, buffer: &mut [u8]) -> Option<(usize, SocketAddr)>;
+    /// Blocks until a datagram is waiting or the non zero timeout passed
+    fn wait(&self, timeout: Duration);
+    fn local_address(&self) -> SocketAddr;
+    /// Drops everything that was received but not yet read, like a restarted process would
+    fn reset(self: Box<Self>) -> Box<dyn Transport>;
+}
+
+#[derive(Debug)]
+pub struct UdpTransport {
+    socket: UdpSocket,
+}
+
+impl UdpTransport {
+    pub fn new(socket: UdpSocket) -> Self {
+        socket.set_nonblocking(true).unwrap();
+        Self { socket }
+    }
+
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send_to(&self, bytes: &[u8], to: &SocketAddr) {
+        self.socket.send_to(bytes, to).unwrap();
+    }
+
+    fn recv_from(&self, buffer: &mut [u8]) -> Option<(usize, SocketAddr)> {
+        match self.socket.recv_from(buffer) {
+            Ok(bytes) => Some(bytes),
+            Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock) => None,
+            Err(e) => panic!("{:?}", e),
+        }
+    }
+
+    fn wait(&self, timeout: Duration) {
+        self.socket.set_nonblocking(false).unwrap();
+        self.socket.set_read_timeout(Some(timeout)).unwrap();
+        // Errors include the timeout and datagrams larger than the buffer, both end the wait
+        let _ = self.socket.peek_from(&mut [0; 1]);
+        self.socket.set_nonblocking(true).unwrap();
+    }
+
+    fn local_address(&self) -> SocketAddr {
+        self.socket.local_addr().unwrap()
+    }
+
+    fn reset(self: Box<Self>) -> Box<dyn Transport> {
+        let address = self.socket.local_addr().unwrap();
+        std::mem::drop(self);
+        Box::new(UdpTransport::new(UdpSocket::bind(address).unwrap()))
+    }
+}
+
+type Mailbox = VecDeque<(Vec<u8>, SocketAddr)>;
+
+/// In-process network, every bound transport gets its own mailbox
+#[derive(Debug, Clone, Default)]
+pub struct MemoryNetwork {
+    mailboxes: Arc<Mutex<HashMap<SocketAddr, Mailbox>>>,
+    /// Signalled on every sent datagram
+    delivered: Arc<Condvar>,
+}
+
+impl MemoryNetwork {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds the next free port on 127.0.0.1, addresses are only meaningful inside this network
+    pub fn bind(&self) -> MemoryTransport {
+        let mut mailboxes = self.mailboxes.lock().unwrap();
+        let port = (1..=u16::MAX)
+            .find(|port| {
+                !mailboxes.contains_key(&SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), *port))
+            })
+            .expect("Memory network is out of ports");
+        let address = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+        mailboxes.insert(address, VecDeque::new());
+        MemoryTransport {
+            network: self.clone(),
+            address,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MemoryTransport {
+    network: MemoryNetwork,
+    address: SocketAddr,
+}
+
+impl Transport for MemoryTransport {
+    fn send_to(&self, bytes: &[u8], to: &SocketAddr) {
+        // Like UDP, datagrams to unbound addresses are silently lost
+        if let Some(mailbox) = self.network.mailboxes.lock().unwrap().get_mut(to) {
+            mailbox.push_back((bytes.to_vec(), self.address));
+            self.network.delivered.notify_all();
+        }
+    }
+
+    fn recv_from(&self, buffer: &mut [u8]) -> Option<(usize, SocketAddr)> {
+        let (bytes, from) = self
+            .network
+            .mailboxes
+            .lock()
+            .unwrap()
+            .get_mut(&self.address)?
+            .pop_front()?;
+        let len = bytes.len().min(buffer.len());
+        buffer[..len].copy_from_slice(&bytes[..len]);
+        Some((len, from))
+    }
+
+    fn wait(&self, timeout: Duration) {
+        let mailboxes = self.network.mailboxes.lock().unwrap();
+        let _ = self
+            .network
+            .delivered
+            .wait_timeout_while(mailboxes, timeout, |mailboxes| {
+                mailboxes
+                    .get(&self.address)
+                    .is_some_and(|mailbox| mailbox.is_empty())
+            })
+            .unwrap();
+    }
+
+    fn local_address(&self) -> SocketAddr {
+        self.address
+    }
+
+    fn reset(self: Box<Self>) -> Box<dyn Transport> {
+        if let Some(mailbox) = self
+            .network
+            .mailboxes
+            .lock()
+            .unwrap()
+            .get_mut(&self.address)
+        {
+            mailbox.clear();
+        }
+        self
+    }
+}
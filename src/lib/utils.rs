@@ -1,5 +1,7 @@
 use std::marker::PhantomData;
+use std::time::Duration;
 
+use rand::Rng;
 use rkyv::{Archive, Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -19,6 +21,14 @@ impl<T> Id<T> {
             _phantom: PhantomData,
         }
     }
+
+    /// Random v4 id drawn from `rng`, a seeded rng gives reproducible ids
+    pub fn from_rng(rng: &mut impl Rng) -> Self {
+        Self {
+            value: uuid::Builder::from_random_bytes(rng.random()).into_uuid(),
+            _phantom: PhantomData,
+        }
+    }
 }
 impl<T> Clone for Id<T> {
     fn clone(&self) -> Self {
@@ -41,6 +51,22 @@ impl<T: EntityType> std::fmt::Display for Id<T> {
     }
 }
 
+/// Serialized as the plain uuid, the entity type is known from the field
+impl<T> serde::Serialize for Id<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.value)
+    }
+}
+
+impl<'de, T> serde::Deserialize<'de> for Id<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self {
+            value: <Uuid as serde::Deserialize>::deserialize(deserializer)?,
+            _phantom: PhantomData,
+        })
+    }
+}
+
 impl<T> Ord for Id<T> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         self.value.cmp(&other.value)
@@ -52,3 +78,22 @@ impl<T> PartialOrd for Id<T> {
         Some(self.cmp(other))
     }
 }
+
+/// Parses durations like `250ms`, `8s`, `1.5s` or `2m`
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit) = value
+        .find(|c: char| c.is_alphabetic())
+        .map(|index| value.split_at(index))
+        .ok_or_else(|| format!("Duration {value} is missing a unit"))?;
+    let number = number
+        .parse::<f64>()
+        .map_err(|_| format!("Invalid duration {value}"))?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "s" => number,
+        "m" => number * 60.0,
+        _ => Err(format!("Unknown duration unit {unit} in {value}"))?,
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|e| format!("Invalid duration {value}: {e}"))
+}
@@ -1,26 +1,107 @@
+pub mod analysis;
+pub mod dashboard;
+pub mod gantt;
+pub mod history;
+pub mod trace;
+pub mod tui;
+
+use std::collections::VecDeque;
 use std::net::SocketAddr;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use colored::{ColoredString, Colorize};
 use rkyv::{Archive, Deserialize, Serialize};
+use uuid::Uuid;
 
 use crate::KEEP_ALIVE_TIMEOUT;
-use crate::lib::fork::ForkRef;
-use crate::lib::messages::VisualizerMessages;
+use crate::lib::events::EventSink;
+use crate::lib::fork::{Fork, ForkRef};
 use crate::lib::messages::visualizer_messages::{
     VisualizerForkState, VisualizerThinkerAvailableTokenState, VisualizerThinkerState,
 };
-use crate::lib::thinker::ThinkerRef;
+use crate::lib::messages::{
+    ControlMessage, ForkMessages, MessageKind, ThinkerMessage, VisualizerMessages,
+};
+use crate::lib::thinker::{Thinker, ThinkerRef};
 use crate::lib::transceiver::Transceiver;
+use crate::lib::utils::Id;
+use dashboard::Dashboard;
+use trace::TraceWriter;
+
+const MAX_EVENTS: usize = 500;
 
-#[derive(Archive, Serialize, Deserialize, Clone, Debug)]
+/// Update kinds a visualizer can filter on, named like `MessageKind::kind`
+pub const UPDATE_KINDS: [&str; 2] = ["ThinkerStateChanged", "ForkStateChanged"];
+
+#[derive(Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub struct VisualizerRef {
     pub address: SocketAddr,
+    /// Only these update kinds are sent, all of them if empty
+    pub kinds: Vec<String>,
+}
+
+impl VisualizerRef {
+    pub fn wants(&self, message: &VisualizerMessages) -> bool {
+        self.kinds.is_empty() || self.kinds.iter().any(|kind| kind == message.kind())
+    }
+}
+
+/// Visualizers renew every `KEEP_ALIVE_TIMEOUT`, after a few missed renewals they are gone
+const SUBSCRIPTION_TIMEOUT: Duration = KEEP_ALIVE_TIMEOUT.saturating_mul(3);
+
+/// Visualizers that joined with `Subscribe`, unlike those of the init they expire
+///
+/// They are not kept across crashes, a running visualizer subscribes again in time.
+#[derive(Debug, Default)]
+pub struct Subscribers(Vec<(VisualizerRef, Instant)>);
+
+impl Subscribers {
+    /// A visualizer that subscribes again renews and replaces its filter, a visualizer of the
+    /// init only replaces its filter
+    pub fn subscribe(&mut self, visualizers: &mut [VisualizerRef], visualizer: VisualizerRef) {
+        if let Some(known) = visualizers
+            .iter_mut()
+            .find(|known| known.address == visualizer.address)
+        {
+            *known = visualizer;
+            return;
+        }
+        self.0
+            .retain(|(subscribed, _)| subscribed.address != visualizer.address);
+        self.0.push((visualizer, Instant::now()));
+    }
+
+    pub fn expire(&mut self) {
+        self.0
+            .retain(|(_, renewed_at)| renewed_at.elapsed() < SUBSCRIPTION_TIMEOUT);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &VisualizerRef> {
+        self.0.iter().map(|(visualizer, _)| visualizer)
+    }
+}
+
+/// Sends the update to every visualizer whose filter accepts it
+pub fn notify<'a>(
+    visualizers: impl IntoIterator<Item = &'a VisualizerRef>,
+    transceiver: &Transceiver,
+    message: VisualizerMessages,
+) {
+    visualizers
+        .into_iter()
+        .filter(|visualizer| visualizer.wants(&message))
+        .for_each(|visualizer| transceiver.send(message.clone(), &visualizer.address));
 }
 
 #[derive(Debug)]
 struct ThinkerState {
     thinker: ThinkerRef,
+    /// Known once the thinker sent an update
+    forks: Option<[Id<Fork>; 2]>,
     visualizer_thinker_state: VisualizerThinkerState,
     visualizer_available_token_state: Vec<VisualizerThinkerAvailableTokenState>,
     last_seen: Instant,
@@ -30,24 +111,135 @@ struct ThinkerState {
 struct ForkState {
     fork: ForkRef,
     visualizer_fork_state: VisualizerForkState,
+    queue: Vec<Id<Thinker>>,
     last_seen: Instant,
 }
 
+/// Sent updates without being part of the topology, e.g. a node left over from a previous run
+#[derive(Debug)]
+pub struct UnregisteredNode {
+    pub kind: &'static str,
+    pub id: Uuid,
+    pub address: SocketAddr,
+    pub state: &'static str,
+    pub last_seen: Instant,
+}
+
+#[derive(Debug, Clone)]
+pub struct VisualizerEvent {
+    pub at: Instant,
+    pub description: String,
+}
+
 #[derive(Debug)]
 pub struct Visualizer {
     transceiver: Transceiver,
     thinkers: Vec<ThinkerState>,
     forks: Vec<ForkState>,
+    unregistered: Vec<UnregisteredNode>,
+    events: VecDeque<VisualizerEvent>,
+    event_sinks: Vec<EventSink<VisualizerEvent>>,
+    /// Wall clock while live, moved along the recording while replaying
+    now: Instant,
+    recorder: Option<TraceWriter>,
+    dashboard: Option<Dashboard>,
+    /// Started without a topology, nodes are added as their updates arrive
+    discovering: bool,
+    subscriptions: Subscriptions,
+}
+
+/// Nodes are subscribed again periodically, restarted nodes forget their visualizer
+#[derive(Debug, Default)]
+struct Subscriptions {
+    thinkers: Vec<SocketAddr>,
+    forks: Vec<SocketAddr>,
+    kinds: Vec<String>,
+    last_sent: Option<Instant>,
+}
+
+/// Thinker `i` sits between fork `i` and fork `i + 1`, like the init server assigns them
+///
+/// Returns the indices of the thinkers in ring order, thinkers with unknown forks come last.
+pub fn ring_order(forks_of_thinkers: &[Option<[Id<Fork>; 2]>]) -> Vec<usize> {
+    let left_of = |index: usize| forks_of_thinkers[index].as_ref().map(|[left, _]| left);
+    let right_of = |index: usize| forks_of_thinkers[index].as_ref().map(|[_, right]| right);
+    let mut remaining = (0..forks_of_thinkers.len()).collect::<Vec<_>>();
+    let mut ordered = vec![];
+    while !remaining.is_empty() {
+        let successor = ordered
+            .last()
+            .and_then(|last| right_of(*last))
+            .and_then(|right| {
+                remaining
+                    .iter()
+                    .position(|index| left_of(*index) == Some(right))
+            });
+        // Crashed thinkers leave gaps, every gap starts a new chain
+        let chain_start = || {
+            remaining.iter().position(|index| {
+                left_of(*index).is_some_and(|left| {
+                    !remaining.iter().any(|other| right_of(*other) == Some(left))
+                })
+            })
+        };
+        let known = || {
+            remaining
+                .iter()
+                .position(|index| forks_of_thinkers[*index].is_some())
+        };
+        let next = successor.or_else(chain_start).or_else(known).unwrap_or(0);
+        ordered.push(remaining.remove(next));
+    }
+    ordered
+}
+
+impl VisualizerThinkerState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            VisualizerThinkerState::Thinking => "Thinking",
+            VisualizerThinkerState::Hungry => "Hungry",
+            VisualizerThinkerState::WaitingForForks { .. } => "WaitingForForks",
+            VisualizerThinkerState::Eating { .. } => "Eating",
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            VisualizerThinkerState::Thinking => "🤔",
+            VisualizerThinkerState::Hungry => "😩",
+            VisualizerThinkerState::WaitingForForks { .. } => "💤",
+            VisualizerThinkerState::Eating { .. } => "🧀",
+        }
+    }
+}
+
+impl VisualizerForkState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            VisualizerForkState::Unused => "Unused",
+            VisualizerForkState::Used(_) => "Used",
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            VisualizerForkState::Unused => "🔓",
+            VisualizerForkState::Used(_) => "🔒",
+        }
+    }
 }
 
 impl Visualizer {
+    /// Without thinkers and forks the topology is discovered from the updates
     pub fn new(transceiver: Transceiver, thinkers: Vec<ThinkerRef>, forks: Vec<ForkRef>) -> Self {
+        let discovering = thinkers.is_empty() && forks.is_empty();
         Self {
             transceiver,
             thinkers: thinkers
                 .into_iter()
                 .map(|thinker| ThinkerState {
                     thinker,
+                    forks: None,
                     visualizer_thinker_state: VisualizerThinkerState::Thinking,
                     last_seen: Instant::now(),
                     visualizer_available_token_state: vec![],
@@ -58,17 +250,240 @@ impl Visualizer {
                 .map(|fork| ForkState {
                     fork,
                     visualizer_fork_state: VisualizerForkState::Unused,
+                    queue: vec![],
                     last_seen: Instant::now(),
                 })
                 .collect(),
+            unregistered: vec![],
+            events: VecDeque::new(),
+            event_sinks: vec![],
+            now: Instant::now(),
+            recorder: None,
+            dashboard: None,
+            discovering,
+            subscriptions: Subscriptions::default(),
+        }
+    }
+
+    /// Asks running nodes to send their updates here as well, renewed until the visualizer stops
+    pub fn subscribe(
+        &mut self,
+        thinkers: Vec<SocketAddr>,
+        forks: Vec<SocketAddr>,
+        kinds: Vec<String>,
+    ) {
+        self.subscriptions = Subscriptions {
+            thinkers,
+            forks,
+            kinds,
+            last_sent: None,
+        };
+        self.renew_subscriptions();
+    }
+
+    fn renew_subscriptions(&mut self) {
+        let subscriptions = &mut self.subscriptions;
+        if subscriptions
+            .last_sent
+            .is_some_and(|last_sent| last_sent.elapsed() < KEEP_ALIVE_TIMEOUT)
+        {
+            return;
+        }
+        subscriptions.last_sent = Some(Instant::now());
+        for address in &subscriptions.thinkers {
+            let kinds = subscriptions.kinds.clone();
+            self.transceiver
+                .send_reliable(ThinkerMessage::Subscribe { kinds }, address);
+        }
+        for address in &subscriptions.forks {
+            let kinds = subscriptions.kinds.clone();
+            self.transceiver
+                .send_reliable(ForkMessages::Subscribe { kinds }, address);
         }
     }
 
+    /// Sorts thinkers along the fork assignment and every fork in front of the thinker to its right
+    fn order_ring(&mut self) {
+        let order = ring_order(
+            &self
+                .thinkers
+                .iter()
+                .map(|thinker_state| thinker_state.forks.clone())
+                .collect::<Vec<_>>(),
+        );
+        let mut thinkers = std::mem::take(&mut self.thinkers)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        self.thinkers = order
+            .into_iter()
+            .map(|index| thinkers[index].take().unwrap())
+            .collect();
+        let thinkers = &self.thinkers;
+        self.forks.sort_by_key(|fork_state| {
+            thinkers
+                .iter()
+                .position(|thinker_state| {
+                    thinker_state
+                        .forks
+                        .as_ref()
+                        .is_some_and(|[left, _]| left.eq(&fork_state.fork.id))
+                })
+                .unwrap_or(usize::MAX)
+        });
+    }
+
+    /// Serves the browser dashboard, returns the bound address
+    pub fn serve_dashboard(&mut self, address: SocketAddr) -> std::io::Result<SocketAddr> {
+        let (dashboard, address) = Dashboard::serve(address)?;
+        dashboard.publish_snapshot(self.snapshot());
+        self.dashboard = Some(dashboard);
+        Ok(address)
+    }
+
+    /// Writes every received message to the trace from now on
+    pub fn record(&mut self, recorder: TraceWriter) {
+        self.recorder = Some(recorder);
+    }
+
+    pub fn set_now(&mut self, now: Instant) {
+        self.now = now;
+    }
+
+    pub fn now(&self) -> Instant {
+        self.now
+    }
+
+    pub fn since(&self, instant: Instant) -> Duration {
+        self.now.saturating_duration_since(instant)
+    }
+
     pub fn tick(&mut self, buffer: &mut [u8]) {
+        self.receive(buffer);
+        self.print_state();
+    }
+
+    pub fn receive(&mut self, buffer: &mut [u8]) {
+        self.now = Instant::now();
+        self.renew_subscriptions();
         while let Some((message, entity)) = self.transceiver.receive::<VisualizerMessages>(buffer) {
+            let message = match &mut self.recorder {
+                Some(recorder) => recorder.write(entity, message),
+                None => message,
+            };
             self.handle_message(message, entity);
         }
-        self.print_state();
+        if let Some(recorder) = &mut self.recorder {
+            recorder.flush();
+        }
+        if let Some(dashboard) = &self.dashboard {
+            dashboard.publish_snapshot(self.snapshot());
+        }
+    }
+
+    /// When `receive` has to run again, at the latest after `interval`
+    pub fn next_deadline(&self, interval: Duration) -> Instant {
+        let deadline = Instant::now() + interval;
+        self.transceiver
+            .next_delivery()
+            .map_or(deadline, |deliver_at| deliver_at.min(deadline))
+    }
+
+    pub fn events(&self) -> impl DoubleEndedIterator<Item = &VisualizerEvent> {
+        self.events.iter()
+    }
+
+    pub fn unregistered(&self) -> impl Iterator<Item = &UnregisteredNode> {
+        self.unregistered.iter()
+    }
+
+    fn track_unregistered(
+        &mut self,
+        kind: &'static str,
+        id: Uuid,
+        address: SocketAddr,
+        state: &'static str,
+    ) {
+        let now = self.now;
+        match self
+            .unregistered
+            .iter_mut()
+            .find(|node| node.kind == kind && node.id == id)
+        {
+            Some(node) => {
+                node.address = address;
+                node.state = state;
+                node.last_seen = now;
+            }
+            None => {
+                self.push_event(format!(
+                    "Update from unregistered {kind} {} at {address}",
+                    &id.to_string()[..4]
+                ));
+                self.unregistered.push(UnregisteredNode {
+                    kind,
+                    id,
+                    address,
+                    state,
+                    last_seen: now,
+                });
+            }
+        }
+    }
+
+    fn push_event(&mut self, description: String) {
+        if let Some(dashboard) = &self.dashboard {
+            dashboard.publish_change(&description);
+        }
+        if self.events.len() >= MAX_EVENTS {
+            self.events.pop_front();
+        }
+        let event = VisualizerEvent {
+            at: self.now,
+            description,
+        };
+        for sink in &mut self.event_sinks {
+            sink.send(&event);
+        }
+        self.events.push_back(event);
+    }
+
+    pub fn add_event_sink(&mut self, sink: EventSink<VisualizerEvent>) {
+        self.event_sinks.push(sink);
+    }
+
+    pub fn send_thinker_control(&self, index: usize, message: ControlMessage) {
+        if let Some(thinker_state) = self.thinkers.get(index) {
+            self.transceiver.send_reliable(
+                ThinkerMessage::Control(message),
+                &thinker_state.thinker.address,
+            );
+        }
+    }
+
+    pub fn send_fork_control(&self, index: usize, message: ControlMessage) {
+        if let Some(fork_state) = self.forks.get(index) {
+            fork_state.fork.replicas().for_each(|address| {
+                self.transceiver
+                    .send_reliable(ForkMessages::Control(message.clone()), address)
+            });
+        }
+    }
+
+    fn short_id<T>(id: &Id<T>) -> String {
+        id.value.to_string().get(0..4).unwrap().to_string()
+    }
+
+    fn fork_index(&self, id: &Id<Fork>) -> Option<usize> {
+        self.forks
+            .iter()
+            .position(|fork_state| fork_state.fork.id.eq(id))
+    }
+
+    fn thinker_index(&self, id: &Id<Thinker>) -> Option<usize> {
+        self.thinkers
+            .iter()
+            .position(|thinker_state| thinker_state.thinker.id.eq(id))
     }
 
     pub fn handle_message(&mut self, message: VisualizerMessages, entity: SocketAddr) {
@@ -76,157 +491,251 @@ impl Visualizer {
             VisualizerMessages::Init { .. } => {
                 log::error!("Already initialized but got init message from {entity}");
             }
-            VisualizerMessages::ForkStateChanged { id, state } => {
-                let el = self
-                    .forks
-                    .iter_mut()
-                    .find(|fork_state| fork_state.fork.id.eq(&id))
-                    .unwrap();
+            VisualizerMessages::ForkStateChanged { id, state, queue } => {
+                if self.discovering && self.fork_index(&id).is_none() {
+                    self.discover_fork(ForkRef {
+                        address: entity,
+                        id: id.clone(),
+                        backups: vec![],
+                    });
+                }
+                let Some(index) = self.fork_index(&id) else {
+                    self.track_unregistered("fork", id.value, entity, state.name());
+                    return;
+                };
+                let el = &mut self.forks[index];
+                let previous_holder = match &el.visualizer_fork_state {
+                    VisualizerForkState::Unused => None,
+                    VisualizerForkState::Used(holder) => Some(holder.clone()),
+                };
+                let revived = self.now.saturating_duration_since(el.last_seen) > KEEP_ALIVE_TIMEOUT;
                 el.visualizer_fork_state = state;
-                el.last_seen = Instant::now();
+                el.last_seen = self.now;
+                el.queue = queue;
+                let holder = match &self.forks[index].visualizer_fork_state {
+                    VisualizerForkState::Unused => None,
+                    VisualizerForkState::Used(holder) => Some(holder.clone()),
+                };
+                if revived {
+                    self.push_event(format!("Fork {index} is alive again"));
+                }
+                if holder != previous_holder {
+                    self.push_event(
+                        match holder.and_then(|holder| self.thinker_index(&holder)) {
+                            Some(thinker_index) => {
+                                format!("Fork {index} taken by thinker {thinker_index}")
+                            }
+                            None => format!("Fork {index} released"),
+                        },
+                    );
+                }
             }
             VisualizerMessages::ThinkerStateChanged {
                 id,
+                forks,
                 state,
                 token_state,
             } => {
-                let el = self
-                    .thinkers
-                    .iter_mut()
-                    .find(|thinker_state| thinker_state.thinker.id.eq(&id))
-                    .unwrap();
+                if self.discovering && self.thinker_index(&id).is_none() {
+                    self.discover_thinker(ThinkerRef {
+                        address: entity,
+                        id: id.clone(),
+                    });
+                }
+                let Some(index) = self.thinker_index(&id) else {
+                    self.track_unregistered("thinker", id.value, entity, state.name());
+                    return;
+                };
+                let fork_ids = forks.clone().map(|fork| fork.id);
+                if self.thinkers[index].forks.as_ref() != Some(&fork_ids) {
+                    self.thinkers[index].forks = Some(fork_ids);
+                    if self.discovering {
+                        // Thinkers know all replicas, fork updates only come from the primary
+                        for fork in forks {
+                            match self.fork_index(&fork.id) {
+                                Some(fork_index) => self.forks[fork_index].fork = fork,
+                                None => self.discover_fork(fork),
+                            }
+                        }
+                    }
+                    self.order_ring();
+                }
+                let index = self.thinker_index(&id).unwrap();
+                let el = &mut self.thinkers[index];
+                let previous_state = el.visualizer_thinker_state.name();
+                let revived = self.now.saturating_duration_since(el.last_seen) > KEEP_ALIVE_TIMEOUT;
                 el.visualizer_thinker_state = state;
-                el.last_seen = Instant::now();
+                el.last_seen = self.now;
                 el.visualizer_available_token_state = token_state;
+                let new_state = self.thinkers[index].visualizer_thinker_state.name();
+                if revived {
+                    self.push_event(format!("Thinker {index} is alive again"));
+                }
+                if previous_state != new_state {
+                    self.push_event(format!("Thinker {index}: {previous_state} -> {new_state}"));
+                }
             }
         }
     }
 
-    pub fn print_state(&self) {
-        print!("\x1B[2J\x1B[1;1H");
-        self.thinkers
-            .iter()
-            .zip(&self.forks)
-            .for_each(|(thinker_state, fork_state)| {
-                enum UsedBy {
-                    Above,
-                    Bellow,
-                }
-
-                let fork_side = match &fork_state.visualizer_fork_state {
-                    VisualizerForkState::Unused => None,
-                    VisualizerForkState::Used(id) if id.eq(&thinker_state.thinker.id) => {
-                        Some(UsedBy::Above)
-                    }
-                    // should not happen that is it not the next one if order is not messed up
-                    VisualizerForkState::Used(_) => Some(UsedBy::Bellow),
-                };
-                match &fork_side {
-                    Some(UsedBy::Bellow) if fork_state.last_seen.elapsed() < KEEP_ALIVE_TIMEOUT => {
-                        println!("⬆️")
-                    }
-                    _ => println!(),
-                };
-
-                let fork_state_char = match fork_state.visualizer_fork_state {
-                    VisualizerForkState::Unused => "🔓",
-                    VisualizerForkState::Used(_) => "🔒",
-                };
-                let fork_state_str = match fork_state.visualizer_fork_state {
-                    VisualizerForkState::Unused => "Unused",
-                    VisualizerForkState::Used(_) => "Used",
-                };
-                // Fork
-                let message = format!(
-                    "🍴 [{}][{:-^15}]    {}",
-                    fork_state_char, fork_state_str, fork_state.fork.id
-                );
-                println!(
-                    "{}",
-                    match fork_state.last_seen.elapsed().cmp(&KEEP_ALIVE_TIMEOUT) {
-                        std::cmp::Ordering::Less | std::cmp::Ordering::Equal =>
-                            ColoredString::from(format!(
-                                "{} ({:?})",
-                                message,
-                                fork_state.last_seen.elapsed()
-                            )),
-                        std::cmp::Ordering::Greater => ColoredString::from(format!(
-                            "{} {}",
-                            message.strikethrough().dimmed(),
-                            "(dead)".red()
-                        )),
-                    }
-                );
+    fn discover_thinker(&mut self, thinker: ThinkerRef) {
+        self.push_event(format!(
+            "Discovered thinker {}",
+            Self::short_id(&thinker.id)
+        ));
+        self.thinkers.push(ThinkerState {
+            thinker,
+            forks: None,
+            visualizer_thinker_state: VisualizerThinkerState::Thinking,
+            visualizer_available_token_state: vec![],
+            last_seen: self.now,
+        });
+    }
 
-                match &fork_side {
-                    Some(UsedBy::Above) if fork_state.last_seen.elapsed() < KEEP_ALIVE_TIMEOUT => {
-                        println!("⬇️")
-                    }
-                    _ => println!(),
-                };
+    fn discover_fork(&mut self, fork: ForkRef) {
+        self.push_event(format!("Discovered fork {}", Self::short_id(&fork.id)));
+        self.forks.push(ForkState {
+            fork,
+            visualizer_fork_state: VisualizerForkState::Unused,
+            queue: vec![],
+            last_seen: self.now,
+        });
+        self.order_ring();
+    }
 
-                let thinker_state_char = match thinker_state.visualizer_thinker_state {
-                    VisualizerThinkerState::Thinking => "🤔",
-                    VisualizerThinkerState::Hungry => "😩",
-                    VisualizerThinkerState::WaitingForForks { .. } => "💤",
-                    VisualizerThinkerState::Eating { .. } => "🧀",
-                };
-                let visualizer_state_str = match thinker_state.visualizer_thinker_state {
-                    VisualizerThinkerState::Thinking => "Thinking",
-                    VisualizerThinkerState::Hungry => "Hungry",
-                    VisualizerThinkerState::WaitingForForks { .. } => "WaitingForForks",
-                    VisualizerThinkerState::Eating { .. } => "Eating",
-                };
-                let message = format!(
-                    "🧐 [{}][{:-^15}] {}",
-                    thinker_state_char, visualizer_state_str, thinker_state.thinker.id
-                );
-                // Thinker
+    /// Forks and thinkers in ring order, positions without a fork or thinker are left out
+    pub fn print_state(&self) {
+        print!("\x1B[2J\x1B[1;1H");
+        for position in 0..self.thinkers.len().max(self.forks.len()) {
+            let thinker_state = self.thinkers.get(position);
+            match self.forks.get(position) {
+                Some(fork_state) => self.print_fork(fork_state, position),
+                None => println!("\n\n"),
+            }
+            if let Some(thinker_state) = thinker_state {
+                self.print_thinker(thinker_state);
+            }
+        }
+        if !self.unregistered.is_empty() {
+            println!();
+            println!("Unregistered:");
+            for node in &self.unregistered {
                 println!(
-                    "{} [tnsf: {}] [{}]",
-                    match thinker_state.last_seen.elapsed().cmp(&KEEP_ALIVE_TIMEOUT) {
-                        std::cmp::Ordering::Less | std::cmp::Ordering::Equal =>
-                            ColoredString::from(format!(
-                                "{} ({:>4}ms)",
-                                message,
-                                thinker_state.last_seen.elapsed().as_millis()
-                            )),
-                        std::cmp::Ordering::Greater => ColoredString::from(format!(
-                            "{} {}",
-                            message.strikethrough().dimmed(),
-                            "(dead)".red()
-                        )),
-                    },
-                    thinker_state
-                        .visualizer_available_token_state
-                        .iter()
-                        .map(|el| match el {
-                            VisualizerThinkerAvailableTokenState::Passive { not_seen_for } =>
-                                format!("{:>4?}ms", not_seen_for.as_millis()),
-                            VisualizerThinkerAvailableTokenState::Propose {
-                                propose_version,
-                                token_version,
-                            } => {
-                                format!(" p{propose_version}->v{token_version} ")
-                            }
-                        })
-                        .collect::<Vec<String>>()
-                        .join(","),
-                    match &thinker_state.visualizer_thinker_state {
-                        VisualizerThinkerState::Thinking => "".to_string(),
-                        VisualizerThinkerState::Hungry => "".to_string(),
-                        VisualizerThinkerState::WaitingForForks { token }
-                        | VisualizerThinkerState::Eating { token } => format!(
-                            "tv: {}, id: {:4}",
-                            token.version,
-                            token.id.value.to_string().get(0..4).unwrap()
-                        ),
-                    }
+                    "  {:<7} {} at {} [{}] ({}ms)",
+                    node.kind,
+                    node.id,
+                    node.address,
+                    node.state,
+                    self.since(node.last_seen).as_millis()
                 );
-            });
+            }
+        }
         println!();
         println!("tnsf = token not seen for");
         println!("tv = token version");
         println!("p{{propose version number}}->v{{token version number}}");
     }
+
+    /// Arrows point to the holder if it is one of the two thinkers next to the fork
+    fn print_fork(&self, fork_state: &ForkState, position: usize) {
+        enum UsedBy {
+            Above,
+            Bellow,
+        }
+
+        let is_thinker_at = |position: Option<usize>, id: &Id<Thinker>| {
+            position
+                .and_then(|position| self.thinkers.get(position))
+                .is_some_and(|thinker_state| thinker_state.thinker.id.eq(id))
+        };
+        let previous = match position {
+            0 => self.thinkers.len().checked_sub(1),
+            _ => Some(position - 1),
+        };
+        let fork_side = match &fork_state.visualizer_fork_state {
+            VisualizerForkState::Unused => None,
+            VisualizerForkState::Used(id) if is_thinker_at(Some(position), id) => {
+                Some(UsedBy::Above)
+            }
+            VisualizerForkState::Used(id) if is_thinker_at(previous, id) => Some(UsedBy::Bellow),
+            VisualizerForkState::Used(_) => None,
+        };
+        let alive = self.since(fork_state.last_seen) < KEEP_ALIVE_TIMEOUT;
+        match &fork_side {
+            Some(UsedBy::Bellow) if alive => println!("⬆️"),
+            _ => println!(),
+        };
+
+        let fork_state_char = fork_state.visualizer_fork_state.symbol();
+        let fork_state_str = fork_state.visualizer_fork_state.name();
+        let message = format!(
+            "🍴 [{}][{:-^15}]    {}",
+            fork_state_char, fork_state_str, fork_state.fork.id
+        );
+        println!(
+            "{}",
+            match self.since(fork_state.last_seen).cmp(&KEEP_ALIVE_TIMEOUT) {
+                std::cmp::Ordering::Less | std::cmp::Ordering::Equal => ColoredString::from(
+                    format!("{} ({:?})", message, self.since(fork_state.last_seen))
+                ),
+                std::cmp::Ordering::Greater => ColoredString::from(format!(
+                    "{} {}",
+                    message.strikethrough().dimmed(),
+                    "(dead)".red()
+                )),
+            }
+        );
+
+        match &fork_side {
+            Some(UsedBy::Above) if alive => println!("⬇️"),
+            _ => println!(),
+        };
+    }
+
+    fn print_thinker(&self, thinker_state: &ThinkerState) {
+        let thinker_state_char = thinker_state.visualizer_thinker_state.symbol();
+        let visualizer_state_str = thinker_state.visualizer_thinker_state.name();
+        let message = format!(
+            "🧐 [{}][{:-^15}] {}",
+            thinker_state_char, visualizer_state_str, thinker_state.thinker.id
+        );
+        println!(
+            "{} [tnsf: {}] [{}]",
+            match self.since(thinker_state.last_seen).cmp(&KEEP_ALIVE_TIMEOUT) {
+                std::cmp::Ordering::Less | std::cmp::Ordering::Equal =>
+                    ColoredString::from(format!(
+                        "{} ({:>4}ms)",
+                        message,
+                        self.since(thinker_state.last_seen).as_millis()
+                    )),
+                std::cmp::Ordering::Greater => ColoredString::from(format!(
+                    "{} {}",
+                    message.strikethrough().dimmed(),
+                    "(dead)".red()
+                )),
+            },
+            thinker_state
+                .visualizer_available_token_state
+                .iter()
+                .map(|el| match el {
+                    VisualizerThinkerAvailableTokenState::Passive { not_seen_for } =>
+                        format!("{:>4?}ms", not_seen_for.as_millis()),
+                    VisualizerThinkerAvailableTokenState::Propose {
+                        propose_version,
+                        token_version,
+                    } => {
+                        format!(" p{propose_version}->v{token_version} ")
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join(","),
+            match &thinker_state.visualizer_thinker_state {
+                VisualizerThinkerState::Thinking => "".to_string(),
+                VisualizerThinkerState::Hungry => "".to_string(),
+                VisualizerThinkerState::WaitingForForks { token }
+                | VisualizerThinkerState::Eating { token } =>
+                    format!("tv: {}, id: {:4}", token.version, Self::short_id(&token.id)),
+            }
+        );
+    }
 }
@@ -0,0 +1,343 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use super::history::RunHistory;
+use crate::lib::messages::thinker_messages::{Token, TokenRef};
+use crate::lib::messages::visualizer_messages::VisualizerThinkerState;
+use crate::lib::thinker::Thinker;
+use crate::lib::utils::Id;
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+#[derive(Serialize, Debug)]
+pub struct ThinkerSummary {
+    pub index: usize,
+    pub id: Id<Thinker>,
+    pub meals: usize,
+    pub eating_ms: u64,
+    pub crashes: usize,
+}
+
+/// Nearest rank percentiles, all zero without samples
+#[derive(Serialize, Debug, Default)]
+pub struct Percentiles {
+    pub count: usize,
+    pub p50_ms: u64,
+    pub p90_ms: u64,
+    pub p99_ms: u64,
+    pub max_ms: u64,
+}
+
+impl Percentiles {
+    fn new(mut samples: Vec<Duration>) -> Self {
+        samples.sort();
+        let Some(max) = samples.last() else {
+            return Self::default();
+        };
+        let rank = |percentile: f64| {
+            let index = (percentile * samples.len() as f64).ceil() as usize;
+            millis(samples[index.clamp(1, samples.len()) - 1])
+        };
+        Self {
+            count: samples.len(),
+            p50_ms: rank(0.5),
+            p90_ms: rank(0.9),
+            p99_ms: rank(0.99),
+            max_ms: millis(*max),
+        }
+    }
+}
+
+/// From the first proposal for a token until a thinker holds its regenerated version
+#[derive(Serialize, Debug)]
+pub struct TokenLoss {
+    pub token_index: usize,
+    pub token: Option<Id<Token>>,
+    pub started_ms: u64,
+    /// Missing if the token was not regenerated before the trace ended
+    pub regeneration_ms: Option<u64>,
+}
+
+/// Neighbouring thinkers that both reported eating at the same time
+#[derive(Serialize, Debug)]
+pub struct Violation {
+    pub thinkers: [usize; 2],
+    pub started_ms: u64,
+    pub duration_ms: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Summary {
+    pub duration_ms: u64,
+    pub meals: usize,
+    pub meals_per_second: f64,
+    /// Jain's fairness index of the meals per thinker, missing if nobody ate
+    pub fairness: Option<f64>,
+    /// From getting hungry until eating
+    pub wait: Percentiles,
+    pub thinkers: Vec<ThinkerSummary>,
+    pub token_losses: Vec<TokenLoss>,
+    pub violations: Vec<Violation>,
+}
+
+fn holds_token(state: &VisualizerThinkerState) -> Option<&TokenRef> {
+    match state {
+        VisualizerThinkerState::WaitingForForks { token }
+        | VisualizerThinkerState::Eating { token } => Some(token),
+        _ => None,
+    }
+}
+
+fn is_eating(state: &VisualizerThinkerState) -> bool {
+    matches!(state, VisualizerThinkerState::Eating { .. })
+}
+
+impl Summary {
+    pub fn analyze(history: &RunHistory) -> Self {
+        let mut waits = vec![];
+        let thinkers = history
+            .thinkers
+            .iter()
+            .enumerate()
+            .map(|(index, thinker)| {
+                let timeline = &thinker.timeline;
+                let mut summary = ThinkerSummary {
+                    index,
+                    id: thinker.id.clone(),
+                    meals: 0,
+                    eating_ms: 0,
+                    crashes: timeline.crashes.len(),
+                };
+                let mut hungry_since = None;
+                let mut previous = None;
+                for interval in &timeline.states {
+                    let crashed = previous
+                        .is_some_and(|previous| timeline.crashed_between(previous, interval));
+                    if crashed {
+                        hungry_since = None;
+                    }
+                    match &interval.state {
+                        VisualizerThinkerState::Thinking => hungry_since = None,
+                        VisualizerThinkerState::Hungry
+                        | VisualizerThinkerState::WaitingForForks { .. } => {
+                            hungry_since.get_or_insert(interval.start);
+                        }
+                        VisualizerThinkerState::Eating { .. } => {
+                            if let Some(since) = hungry_since.take() {
+                                waits.push(interval.start - since);
+                            }
+                            // A token regenerated during the meal changes the state, not the meal
+                            if crashed || !previous.is_some_and(|p| is_eating(&p.state)) {
+                                summary.meals += 1;
+                            }
+                            summary.eating_ms += millis(interval.end - interval.start);
+                        }
+                    }
+                    previous = Some(interval);
+                }
+                summary
+            })
+            .collect::<Vec<_>>();
+
+        let meals = thinkers.iter().map(|thinker| thinker.meals).sum::<usize>();
+        let squares = thinkers
+            .iter()
+            .map(|thinker| (thinker.meals * thinker.meals) as f64)
+            .sum::<f64>();
+        Self {
+            duration_ms: millis(history.duration),
+            meals,
+            meals_per_second: match history.duration.is_zero() {
+                true => 0.0,
+                false => meals as f64 / history.duration.as_secs_f64(),
+            },
+            fairness: (squares > 0.0)
+                .then(|| (meals * meals) as f64 / (thinkers.len() as f64 * squares)),
+            wait: Percentiles::new(waits),
+            thinkers,
+            token_losses: token_losses(history),
+            violations: violations(history),
+        }
+    }
+}
+
+/// Thinkers only report the index of a proposed token, the id is learned from its regeneration
+fn token_losses(history: &RunHistory) -> Vec<TokenLoss> {
+    enum Observation<'a> {
+        Proposal(usize),
+        Hold(&'a TokenRef),
+    }
+    let mut observations = history
+        .thinkers
+        .iter()
+        .flat_map(|thinker| {
+            let proposals = thinker
+                .proposals
+                .iter()
+                .map(|(at, index)| (*at, Observation::Proposal(*index)));
+            let holds = thinker
+                .timeline
+                .states
+                .iter()
+                .filter_map(|interval| {
+                    holds_token(&interval.state).map(|token| (interval.start, token))
+                })
+                .map(|(at, token)| (at, Observation::Hold(token)));
+            proposals.chain(holds)
+        })
+        .collect::<Vec<_>>();
+    observations.sort_by_key(|(at, _)| *at);
+
+    let mut losses: Vec<TokenLoss> = vec![];
+    let mut versions = BTreeMap::new();
+    let mut token_ids = BTreeMap::new();
+    for (at, observation) in observations {
+        let open = |losses: &[TokenLoss], index: usize| {
+            losses
+                .iter()
+                .position(|loss| loss.token_index == index && loss.regeneration_ms.is_none())
+        };
+        match observation {
+            Observation::Proposal(index) => {
+                if open(&losses, index).is_none() {
+                    losses.push(TokenLoss {
+                        token_index: index,
+                        token: token_ids.get(&index).cloned(),
+                        started_ms: millis(at),
+                        regeneration_ms: None,
+                    });
+                }
+            }
+            Observation::Hold(token) => {
+                let previous = versions.insert(token.id.value, token.version);
+                if previous.is_none_or(|previous| token.version <= previous) {
+                    continue;
+                }
+                let known_index = token_ids
+                    .iter()
+                    .find(|(_, id)| token.id.eq(id))
+                    .map(|(index, _)| *index);
+                let loss = match known_index {
+                    Some(index) => open(&losses, index),
+                    None => losses
+                        .iter()
+                        .position(|loss| loss.regeneration_ms.is_none()),
+                };
+                if let Some(loss) = loss {
+                    let loss = &mut losses[loss];
+                    loss.token = Some(token.id.clone());
+                    loss.regeneration_ms = Some(millis(at) - loss.started_ms);
+                    token_ids.insert(loss.token_index, token.id.clone());
+                }
+            }
+        }
+    }
+    losses
+}
+
+fn violations(history: &RunHistory) -> Vec<Violation> {
+    let count = history.thinkers.len();
+    let mut violations = vec![];
+    // Two thinkers in a ring of two are neighbours only once
+    let pairs = match count {
+        0 | 1 => 0,
+        2 => 1,
+        _ => count,
+    };
+    for left in 0..pairs {
+        let right = (left + 1) % count;
+        let eating = |index: usize| {
+            history.thinkers[index]
+                .timeline
+                .states
+                .iter()
+                .filter(|interval| is_eating(&interval.state))
+        };
+        for left_interval in eating(left) {
+            for right_interval in eating(right) {
+                let start = left_interval.start.max(right_interval.start);
+                let end = left_interval.end.min(right_interval.end);
+                if start <= end {
+                    violations.push(Violation {
+                        thinkers: [left, right],
+                        started_ms: millis(start),
+                        duration_ms: millis(end - start),
+                    });
+                }
+            }
+        }
+    }
+    violations.sort_by_key(|violation| violation.started_ms);
+    violations
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Duration:  {:.1}s", self.duration_ms as f64 / 1000.0)?;
+        writeln!(
+            f,
+            "Meals:     {} ({:.2}/s)",
+            self.meals, self.meals_per_second
+        )?;
+        match self.fairness {
+            Some(fairness) => writeln!(f, "Fairness:  {fairness:.3} (Jain's index)")?,
+            None => writeln!(f, "Fairness:  - (nobody ate)")?,
+        }
+        writeln!(
+            f,
+            "Wait:      p50 {}ms, p90 {}ms, p99 {}ms, max {}ms over {} meals",
+            self.wait.p50_ms, self.wait.p90_ms, self.wait.p99_ms, self.wait.max_ms, self.wait.count
+        )?;
+        writeln!(f)?;
+        writeln!(f, "Thinker  Meals  Eating    Crashes  Id")?;
+        for thinker in &self.thinkers {
+            writeln!(
+                f,
+                "T{:<7} {:<6} {:<9} {:<8} {}",
+                thinker.index,
+                thinker.meals,
+                format!("{}ms", thinker.eating_ms),
+                thinker.crashes,
+                thinker.id.value
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Token losses: {}", self.token_losses.len())?;
+        for loss in &self.token_losses {
+            let token = loss
+                .token
+                .as_ref()
+                .map(|token| token.value.to_string())
+                .unwrap_or_else(|| "?".to_string());
+            match loss.regeneration_ms {
+                Some(regeneration) => writeln!(
+                    f,
+                    "  token {} ({token}) lost at {}ms, regenerated after {regeneration}ms",
+                    loss.token_index, loss.started_ms
+                )?,
+                None => writeln!(
+                    f,
+                    "  token {} ({token}) lost at {}ms, not regenerated",
+                    loss.token_index, loss.started_ms
+                )?,
+            }
+        }
+        writeln!(f)?;
+        writeln!(f, "Neighbour violations: {}", self.violations.len())?;
+        for violation in &self.violations {
+            writeln!(
+                f,
+                "  T{} and T{} both eating at {}ms for {}ms",
+                violation.thinkers[0],
+                violation.thinkers[1],
+                violation.started_ms,
+                violation.duration_ms
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use super::Visualizer;
+use crate::KEEP_ALIVE_TIMEOUT;
+use crate::lib::http::{self, EventBroadcast, Response};
+use crate::lib::messages::visualizer_messages::{
+    VisualizerForkState, VisualizerThinkerAvailableTokenState, VisualizerThinkerState,
+};
+
+const PAGE: &str = include_str!("dashboard.html");
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TokenSnapshot {
+    pub id: String,
+    pub version: u32,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TokenStateSnapshot {
+    Passive {
+        not_seen_for_ms: u128,
+    },
+    Propose {
+        token_version: u32,
+        propose_version: u32,
+    },
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ThinkerSnapshot {
+    pub index: usize,
+    pub id: String,
+    pub address: SocketAddr,
+    pub state: &'static str,
+    pub token: Option<TokenSnapshot>,
+    pub token_states: Vec<TokenStateSnapshot>,
+    pub last_seen_ms: u128,
+    pub alive: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ForkSnapshot {
+    pub index: usize,
+    pub id: String,
+    pub address: SocketAddr,
+    pub backups: Vec<SocketAddr>,
+    pub state: &'static str,
+    /// Ring index of the holding thinker
+    pub holder: Option<usize>,
+    pub queue: Vec<usize>,
+    pub last_seen_ms: u128,
+    pub alive: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct UnregisteredSnapshot {
+    pub kind: &'static str,
+    pub id: String,
+    pub address: SocketAddr,
+    pub state: &'static str,
+    pub last_seen_ms: u128,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct VisualizerSnapshot {
+    pub thinkers: Vec<ThinkerSnapshot>,
+    pub forks: Vec<ForkSnapshot>,
+    pub unregistered: Vec<UnregisteredSnapshot>,
+}
+
+#[derive(Serialize, Debug)]
+struct ChangeEvent<'a> {
+    description: &'a str,
+}
+
+/// State shared with the HTTP threads, updated by the visualizer after every receive
+#[derive(Debug, Clone, Default)]
+pub struct Dashboard {
+    snapshot: Arc<Mutex<VisualizerSnapshot>>,
+    events: EventBroadcast,
+}
+
+impl Dashboard {
+    /// Serves `/` (ring page), `/state` (JSON snapshot) and `/events` (server sent events)
+    pub fn serve(address: SocketAddr) -> std::io::Result<(Self, SocketAddr)> {
+        let dashboard = Self::default();
+        let shared = dashboard.clone();
+        let address = http::serve(address, move |path| match path {
+            "/" | "/index.html" => Response::Ok {
+                content_type: "text/html; charset=utf-8",
+                body: PAGE.to_string(),
+            },
+            "/state" => Response::Ok {
+                content_type: "application/json",
+                body: serde_json::to_string(&*shared.snapshot.lock().unwrap()).unwrap(),
+            },
+            "/events" => Response::EventStream(shared.events.subscribe()),
+            _ => Response::NotFound,
+        })?;
+        Ok((dashboard, address))
+    }
+
+    pub fn publish_change(&self, description: &str) {
+        self.events.publish(
+            "change",
+            &serde_json::to_string(&ChangeEvent { description }).unwrap(),
+        );
+    }
+
+    pub fn publish_snapshot(&self, snapshot: VisualizerSnapshot) {
+        self.events
+            .publish("snapshot", &serde_json::to_string(&snapshot).unwrap());
+        *self.snapshot.lock().unwrap() = snapshot;
+    }
+}
+
+impl Visualizer {
+    pub fn snapshot(&self) -> VisualizerSnapshot {
+        VisualizerSnapshot {
+            thinkers: self
+                .thinkers
+                .iter()
+                .enumerate()
+                .map(|(index, thinker_state)| ThinkerSnapshot {
+                    index,
+                    id: thinker_state.thinker.id.to_string(),
+                    address: thinker_state.thinker.address,
+                    state: thinker_state.visualizer_thinker_state.name(),
+                    token: match &thinker_state.visualizer_thinker_state {
+                        VisualizerThinkerState::Thinking | VisualizerThinkerState::Hungry => None,
+                        VisualizerThinkerState::WaitingForForks { token }
+                        | VisualizerThinkerState::Eating { token } => Some(TokenSnapshot {
+                            id: token.id.to_string(),
+                            version: token.version,
+                        }),
+                    },
+                    token_states: thinker_state
+                        .visualizer_available_token_state
+                        .iter()
+                        .map(|token_state| match token_state {
+                            VisualizerThinkerAvailableTokenState::Passive { not_seen_for } => {
+                                TokenStateSnapshot::Passive {
+                                    not_seen_for_ms: not_seen_for.as_millis(),
+                                }
+                            }
+                            VisualizerThinkerAvailableTokenState::Propose {
+                                token_version,
+                                propose_version,
+                            } => TokenStateSnapshot::Propose {
+                                token_version: *token_version,
+                                propose_version: *propose_version,
+                            },
+                        })
+                        .collect(),
+                    last_seen_ms: self.since(thinker_state.last_seen).as_millis(),
+                    alive: self.since(thinker_state.last_seen) <= KEEP_ALIVE_TIMEOUT,
+                })
+                .collect(),
+            forks: self
+                .forks
+                .iter()
+                .enumerate()
+                .map(|(index, fork_state)| ForkSnapshot {
+                    index,
+                    id: fork_state.fork.id.to_string(),
+                    address: fork_state.fork.address,
+                    backups: fork_state.fork.backups.clone(),
+                    state: fork_state.visualizer_fork_state.name(),
+                    holder: match &fork_state.visualizer_fork_state {
+                        VisualizerForkState::Unused => None,
+                        VisualizerForkState::Used(holder) => self.thinker_index(holder),
+                    },
+                    queue: fork_state
+                        .queue
+                        .iter()
+                        .filter_map(|id| self.thinker_index(id))
+                        .collect(),
+                    last_seen_ms: self.since(fork_state.last_seen).as_millis(),
+                    alive: self.since(fork_state.last_seen) <= KEEP_ALIVE_TIMEOUT,
+                })
+                .collect(),
+            unregistered: self
+                .unregistered()
+                .map(|node| UnregisteredSnapshot {
+                    kind: node.kind,
+                    id: node.id.to_string(),
+                    address: node.address,
+                    state: node.state,
+                    last_seen_ms: self.since(node.last_seen).as_millis(),
+                })
+                .collect(),
+        }
+    }
+}
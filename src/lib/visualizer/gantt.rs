@@ -0,0 +1,211 @@
+use std::fmt::Write;
+use std::time::Duration;
+
+use super::history::{Interval, RunHistory, Timeline};
+use crate::lib::messages::visualizer_messages::VisualizerThinkerState;
+
+const LABEL_WIDTH: f64 = 70.0;
+const ROW_HEIGHT: f64 = 22.0;
+const FORK_ROW_HEIGHT: f64 = 12.0;
+const AXIS_HEIGHT: f64 = 24.0;
+const LEGEND_HEIGHT: f64 = 28.0;
+const MIN_TICK_SPACING: f64 = 80.0;
+const TICK_STEPS: [u64; 10] = [1, 2, 5, 10, 15, 30, 60, 120, 300, 600];
+
+const THINKING_COLOR: &str = "#5b8def";
+const HUNGRY_COLOR: &str = "#e5c07b";
+const WAITING_COLOR: &str = "#c678dd";
+const EATING_COLOR: &str = "#98c379";
+const USED_COLOR: &str = "#e06c75";
+
+const LEGEND: [(&str, &str, &str); 6] = [
+    ("🤔", "Thinking", THINKING_COLOR),
+    ("😩", "Hungry", HUNGRY_COLOR),
+    ("💤", "WaitingForForks", WAITING_COLOR),
+    ("🧀", "Eating", EATING_COLOR),
+    ("🔒", "Used", USED_COLOR),
+    ("💥", "Crashed", "url(#crash)"),
+];
+
+fn thinker_color(state: &VisualizerThinkerState) -> &'static str {
+    match state {
+        VisualizerThinkerState::Thinking => THINKING_COLOR,
+        VisualizerThinkerState::Hungry => HUNGRY_COLOR,
+        VisualizerThinkerState::WaitingForForks { .. } => WAITING_COLOR,
+        VisualizerThinkerState::Eating { .. } => EATING_COLOR,
+    }
+}
+
+/// Intervals are drawn until the next report, or until the last one before a crash
+fn extents<T>(timeline: &Timeline<T>) -> impl Iterator<Item = (&Interval<T>, Duration)> {
+    timeline.states.iter().enumerate().map(|(index, interval)| {
+        let end = match timeline.states.get(index + 1) {
+            Some(next) if !timeline.crashed_between(interval, next) => next.start,
+            _ => interval.end,
+        };
+        (interval, end)
+    })
+}
+
+struct Scale {
+    pixels_per_second: f64,
+}
+
+impl Scale {
+    fn x(&self, at: Duration) -> f64 {
+        LABEL_WIDTH + at.as_secs_f64() * self.pixels_per_second
+    }
+
+    fn width(&self, from: Duration, to: Duration) -> f64 {
+        (to.saturating_sub(from).as_secs_f64() * self.pixels_per_second).max(1.0)
+    }
+}
+
+/// One fork row and one thinker row per ring position, in the order of `Visualizer::print_state`
+///
+/// Token passes are only visible between thinkers that were hungry for the token, so arrows connect consecutive holders.
+pub fn render(history: &RunHistory, width: usize) -> String {
+    let width = width as f64;
+    let scale = Scale {
+        pixels_per_second: (width - LABEL_WIDTH - 10.0) / history.duration.as_secs_f64().max(1.0),
+    };
+    let rows = history.thinkers.len().max(history.forks.len());
+    let fork_y = |index: usize| AXIS_HEIGHT + index as f64 * (ROW_HEIGHT + FORK_ROW_HEIGHT + 4.0);
+    let thinker_y = |index: usize| fork_y(index) + FORK_ROW_HEIGHT + 2.0;
+    let height = fork_y(rows) + LEGEND_HEIGHT;
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" font-family="monospace" font-size="11">"#
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r##"<defs><pattern id="crash" width="6" height="6" patternUnits="userSpaceOnUse" patternTransform="rotate(45)"><rect width="6" height="6" fill="#eee"/><line x1="0" y1="0" x2="0" y2="6" stroke="#999" stroke-width="3"/></pattern><marker id="arrow" viewBox="0 0 6 6" refX="6" refY="3" markerWidth="6" markerHeight="6" orient="auto"><path d="M0,0 L6,3 L0,6 z" fill="#333"/></marker></defs>"##
+    )
+    .unwrap();
+
+    let step = TICK_STEPS
+        .into_iter()
+        .find(|step| *step as f64 * scale.pixels_per_second >= MIN_TICK_SPACING)
+        .unwrap_or(*TICK_STEPS.last().unwrap());
+    for second in (0..=history.duration.as_secs()).step_by(step as usize) {
+        let x = scale.x(Duration::from_secs(second));
+        writeln!(
+            svg,
+            r##"<line x1="{x:.1}" y1="{}" x2="{x:.1}" y2="{:.1}" stroke="#ddd"/><text x="{x:.1}" y="12" text-anchor="middle">{second}s</text>"##,
+            AXIS_HEIGHT - 6.0,
+            fork_y(rows)
+        )
+        .unwrap();
+    }
+
+    for (index, fork) in history.forks.iter().enumerate() {
+        let y = fork_y(index);
+        writeln!(
+            svg,
+            r#"<text x="4" y="{:.1}" dominant-baseline="middle">🍴 f{index}</text>"#,
+            y + FORK_ROW_HEIGHT / 2.0
+        )
+        .unwrap();
+        for (interval, end) in extents(&fork.timeline) {
+            let Some(holder) = interval.state else {
+                continue;
+            };
+            writeln!(
+                svg,
+                r#"<rect x="{:.1}" y="{y:.1}" width="{:.1}" height="{FORK_ROW_HEIGHT}" fill="{USED_COLOR}"><title>f{index} used by T{holder}</title></rect>"#,
+                scale.x(interval.start),
+                scale.width(interval.start, end)
+            )
+            .unwrap();
+        }
+        draw_crashes(&mut svg, &scale, &fork.timeline, y, FORK_ROW_HEIGHT);
+    }
+
+    for (index, thinker) in history.thinkers.iter().enumerate() {
+        let y = thinker_y(index);
+        writeln!(
+            svg,
+            r#"<text x="4" y="{:.1}" dominant-baseline="middle">🧐 T{index}</text>"#,
+            y + ROW_HEIGHT / 2.0
+        )
+        .unwrap();
+        for (interval, end) in extents(&thinker.timeline) {
+            writeln!(
+                svg,
+                r#"<rect x="{:.1}" y="{y:.1}" width="{:.1}" height="{ROW_HEIGHT}" fill="{}"><title>T{index} {} {}</title></rect>"#,
+                scale.x(interval.start),
+                scale.width(interval.start, end),
+                thinker_color(&interval.state),
+                interval.state.symbol(),
+                interval.state.name()
+            )
+            .unwrap();
+        }
+        draw_crashes(&mut svg, &scale, &thinker.timeline, y, ROW_HEIGHT);
+    }
+
+    let mut holds = history
+        .thinkers
+        .iter()
+        .enumerate()
+        .flat_map(|(index, thinker)| {
+            extents(&thinker.timeline).filter_map(move |(interval, end)| match &interval.state {
+                VisualizerThinkerState::WaitingForForks { token }
+                | VisualizerThinkerState::Eating { token } => {
+                    Some((interval.start, end, index, token.id.clone()))
+                }
+                _ => None,
+            })
+        })
+        .collect::<Vec<_>>();
+    holds.sort_by_key(|(start, ..)| *start);
+    for (position, (start, _, index, token)) in holds.iter().enumerate() {
+        let previous = holds[..position]
+            .iter()
+            .rev()
+            .find(|(.., other_token)| other_token.eq(token));
+        if let Some((_, previous_end, previous_index, _)) = previous
+            && previous_index != index
+        {
+            writeln!(
+                svg,
+                r##"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="#333" marker-end="url(#arrow)"/>"##,
+                scale.x(*previous_end),
+                thinker_y(*previous_index) + ROW_HEIGHT / 2.0,
+                scale.x(*start),
+                thinker_y(*index) + ROW_HEIGHT / 2.0
+            )
+            .unwrap();
+        }
+    }
+
+    let legend_y = fork_y(rows) + 8.0;
+    for (position, (symbol, name, color)) in LEGEND.into_iter().enumerate() {
+        let x = LABEL_WIDTH + position as f64 * 140.0;
+        writeln!(
+            svg,
+            r#"<rect x="{x:.1}" y="{legend_y:.1}" width="12" height="12" fill="{color}"/><text x="{:.1}" y="{:.1}" dominant-baseline="middle">{symbol} {name}</text>"#,
+            x + 16.0,
+            legend_y + 6.0
+        )
+        .unwrap();
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn draw_crashes<T>(svg: &mut String, scale: &Scale, timeline: &Timeline<T>, y: f64, height: f64) {
+    for crash in &timeline.crashes {
+        writeln!(
+            svg,
+            r#"<rect x="{:.1}" y="{y:.1}" width="{:.1}" height="{height}" fill="url(#crash)"><title>crashed for {:.1}s</title></rect>"#,
+            scale.x(crash.start),
+            scale.width(crash.start, crash.end),
+            (crash.end - crash.start).as_secs_f64()
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,239 @@
+use std::ops::Range;
+use std::time::Duration;
+
+use super::ring_order;
+use super::trace::TraceRecord;
+use crate::KEEP_ALIVE_TIMEOUT;
+use crate::lib::fork::Fork;
+use crate::lib::messages::VisualizerMessages;
+use crate::lib::messages::visualizer_messages::{
+    VisualizerForkState, VisualizerThinkerAvailableTokenState, VisualizerThinkerState,
+};
+use crate::lib::thinker::Thinker;
+use crate::lib::utils::Id;
+
+/// `start` and `end` are the first and the last report of the state
+#[derive(Debug, Clone)]
+pub struct Interval<T> {
+    pub start: Duration,
+    pub end: Duration,
+    pub state: T,
+}
+
+/// Reported states of one node, silences longer than `KEEP_ALIVE_TIMEOUT` count as crashes
+#[derive(Debug)]
+pub struct Timeline<T> {
+    pub states: Vec<Interval<T>>,
+    pub crashes: Vec<Range<Duration>>,
+}
+
+impl<T> Default for Timeline<T> {
+    fn default() -> Self {
+        Self {
+            states: vec![],
+            crashes: vec![],
+        }
+    }
+}
+
+impl<T> Timeline<T> {
+    /// Whether the node was silent between the two intervals
+    pub fn crashed_between(&self, before: &Interval<T>, after: &Interval<T>) -> bool {
+        self.crashes
+            .iter()
+            .any(|crash| crash.start >= before.end && crash.end <= after.start)
+    }
+}
+
+impl<T: PartialEq> Timeline<T> {
+    fn report(&mut self, at: Duration, state: T) {
+        match self.states.last_mut() {
+            Some(last) if at.saturating_sub(last.end) > KEEP_ALIVE_TIMEOUT => {
+                self.crashes.push(last.end..at);
+            }
+            Some(last) if last.state == state => {
+                last.end = at;
+                return;
+            }
+            _ => (),
+        }
+        self.states.push(Interval {
+            start: at,
+            end: at,
+            state,
+        });
+    }
+
+    fn finish(&mut self, duration: Duration) {
+        if let Some(last) = self.states.last()
+            && duration.saturating_sub(last.end) > KEEP_ALIVE_TIMEOUT
+        {
+            self.crashes.push(last.end..duration);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ThinkerHistory {
+    pub id: Id<Thinker>,
+    pub timeline: Timeline<VisualizerThinkerState>,
+    /// Start of every proposal, with the index of the proposed token in the available tokens
+    pub proposals: Vec<(Duration, usize)>,
+    proposing: Vec<bool>,
+}
+
+#[derive(Debug)]
+pub struct ForkHistory {
+    pub id: Id<Fork>,
+    /// Index of the holding thinker
+    pub timeline: Timeline<Option<usize>>,
+}
+
+/// States of all nodes of a recorded run, thinkers and forks in ring order
+#[derive(Debug)]
+pub struct RunHistory {
+    pub thinkers: Vec<ThinkerHistory>,
+    pub forks: Vec<ForkHistory>,
+    pub duration: Duration,
+}
+
+impl RunHistory {
+    pub fn from_trace(records: &[TraceRecord]) -> Result<Self, String> {
+        let Some((init, records)) = records.split_first() else {
+            return Err("The trace is empty".to_string());
+        };
+        let VisualizerMessages::Init { thinkers, forks } = &init.message else {
+            return Err("The trace does not start with an init message".to_string());
+        };
+        let (thinkers, forks) = match thinkers.is_empty() && forks.is_empty() {
+            // Recorded by a subscribed visualizer
+            true => discover(records),
+            false => (
+                thinkers.iter().map(|thinker| thinker.id.clone()).collect(),
+                forks.iter().map(|fork| fork.id.clone()).collect(),
+            ),
+        };
+        let mut history = RunHistory {
+            thinkers: thinkers
+                .into_iter()
+                .map(|id| ThinkerHistory {
+                    id,
+                    timeline: Timeline::default(),
+                    proposals: vec![],
+                    proposing: vec![],
+                })
+                .collect(),
+            forks: forks
+                .into_iter()
+                .map(|id| ForkHistory {
+                    id,
+                    timeline: Timeline::default(),
+                })
+                .collect(),
+            duration: records.last().unwrap_or(init).elapsed,
+        };
+
+        for record in records {
+            let at = record.elapsed;
+            match &record.message {
+                VisualizerMessages::Init { .. } => {
+                    log::warn!("Ignoring second init message at {at:?}");
+                }
+                VisualizerMessages::ForkStateChanged { id, state, .. } => {
+                    let holder = match state {
+                        VisualizerForkState::Unused => None,
+                        VisualizerForkState::Used(thinker) => history.thinker_index(thinker),
+                    };
+                    match history.forks.iter_mut().find(|fork| fork.id.eq(id)) {
+                        Some(fork) => fork.timeline.report(at, holder),
+                        None => log::warn!("Ignoring update of unknown fork {}", id.value),
+                    }
+                }
+                VisualizerMessages::ThinkerStateChanged {
+                    id,
+                    state,
+                    token_state,
+                    ..
+                } => {
+                    let Some(thinker) = history
+                        .thinkers
+                        .iter_mut()
+                        .find(|thinker| thinker.id.eq(id))
+                    else {
+                        log::warn!("Ignoring update of unknown thinker {}", id.value);
+                        continue;
+                    };
+                    thinker.timeline.report(at, state.clone());
+                    thinker.proposing.resize(token_state.len(), false);
+                    for (index, token_state) in token_state.iter().enumerate() {
+                        let proposing = matches!(
+                            token_state,
+                            VisualizerThinkerAvailableTokenState::Propose { .. }
+                        );
+                        if proposing && !thinker.proposing[index] {
+                            thinker.proposals.push((at, index));
+                        }
+                        thinker.proposing[index] = proposing;
+                    }
+                }
+            }
+        }
+
+        for thinker in &mut history.thinkers {
+            thinker.timeline.finish(history.duration);
+        }
+        for fork in &mut history.forks {
+            fork.timeline.finish(history.duration);
+        }
+        Ok(history)
+    }
+
+    pub fn thinker_index(&self, id: &Id<Thinker>) -> Option<usize> {
+        self.thinkers.iter().position(|thinker| thinker.id.eq(id))
+    }
+}
+
+/// Every node that sent an update, in ring order
+fn discover(records: &[TraceRecord]) -> (Vec<Id<Thinker>>, Vec<Id<Fork>>) {
+    let mut thinkers: Vec<(Id<Thinker>, [Id<Fork>; 2])> = vec![];
+    let mut forks: Vec<Id<Fork>> = vec![];
+    for record in records {
+        match &record.message {
+            VisualizerMessages::ThinkerStateChanged {
+                id,
+                forks: thinker_forks,
+                ..
+            } => {
+                if !thinkers.iter().any(|(thinker, _)| thinker.eq(id)) {
+                    thinkers.push((id.clone(), thinker_forks.clone().map(|fork| fork.id)));
+                }
+                for fork in thinker_forks {
+                    if !forks.contains(&fork.id) {
+                        forks.push(fork.id.clone());
+                    }
+                }
+            }
+            VisualizerMessages::ForkStateChanged { id, .. } => {
+                if !forks.contains(id) {
+                    forks.push(id.clone());
+                }
+            }
+            VisualizerMessages::Init { .. } => (),
+        }
+    }
+    let forks_of_thinkers = thinkers
+        .iter()
+        .map(|(_, forks)| Some(forks.clone()))
+        .collect::<Vec<_>>();
+    let thinkers = ring_order(&forks_of_thinkers)
+        .into_iter()
+        .map(|index| thinkers[index].clone())
+        .collect::<Vec<_>>();
+    forks.sort_by_key(|fork| {
+        thinkers
+            .iter()
+            .position(|(_, [left, _])| left.eq(fork))
+            .unwrap_or(usize::MAX)
+    });
+    (thinkers.into_iter().map(|(id, _)| id).collect(), forks)
+}
@@ -0,0 +1,283 @@
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::mpsc::{Receiver, channel};
+use std::time::{Duration, Instant};
+
+use rkyv::{Archive, Deserialize, Serialize};
+
+use super::Visualizer;
+use crate::TICK_INTERVAL;
+use crate::lib::messages::VisualizerMessages;
+use crate::lib::transceiver::Transceiver;
+use crate::lib::transport::MemoryNetwork;
+use crate::lib::utils::parse_duration;
+
+/// One received visualizer message, `elapsed` counts from the start of the recording
+#[derive(Archive, Serialize, Deserialize, Debug)]
+pub struct TraceRecord {
+    pub elapsed: Duration,
+    pub sender: SocketAddr,
+    pub message: VisualizerMessages,
+}
+
+/// Appends length prefixed rkyv records to a trace file
+#[derive(Debug)]
+pub struct TraceWriter {
+    file: BufWriter<File>,
+    started_at: Instant,
+}
+
+impl TraceWriter {
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        Ok(Self {
+            file: BufWriter::new(File::create(path)?),
+            started_at: Instant::now(),
+        })
+    }
+
+    /// Hands the message back so it can still be handled
+    pub fn write(&mut self, sender: SocketAddr, message: VisualizerMessages) -> VisualizerMessages {
+        let record = TraceRecord {
+            elapsed: self.started_at.elapsed(),
+            sender,
+            message,
+        };
+        let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&record).unwrap();
+        if let Err(e) = self
+            .file
+            .write_all(&(bytes.len() as u32).to_le_bytes())
+            .and_then(|_| self.file.write_all(&bytes))
+        {
+            log::error!("Could not write trace record: {e}");
+        }
+        record.message
+    }
+
+    pub fn flush(&mut self) {
+        if let Err(e) = self.file.flush() {
+            log::error!("Could not flush trace: {e}");
+        }
+    }
+}
+
+pub fn read_trace(path: &Path) -> Result<Vec<TraceRecord>, String> {
+    let mut bytes = vec![];
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+    let mut records = vec![];
+    let mut rest = bytes.as_slice();
+    while let Some((len, tail)) = rest.split_first_chunk::<4>() {
+        let len = u32::from_le_bytes(*len) as usize;
+        if tail.len() < len {
+            // The recorder was killed in the middle of a write
+            log::warn!("Ignoring truncated record at the end of {}", path.display());
+            break;
+        }
+        let mut aligned = rkyv::util::AlignedVec::<16>::new();
+        aligned.extend_from_slice(&tail[..len]);
+        records.push(
+            rkyv::from_bytes::<TraceRecord, rkyv::rancor::Error>(&aligned)
+                .map_err(|e| format!("Invalid record in {}: {e}", path.display()))?,
+        );
+        rest = &tail[len..];
+    }
+    Ok(records)
+}
+
+/// Parses a playback speed, a finite factor of at least 0
+pub fn parse_speed(value: &str) -> Result<f64, String> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|speed| speed.is_finite() && *speed >= 0.0)
+        .ok_or_else(|| format!("Invalid speed {value}"))
+}
+
+#[derive(Debug)]
+enum ReplayCommand {
+    TogglePause,
+    Speed(f64),
+    Seek(Duration),
+    Forward(Duration),
+    Backward(Duration),
+    Quit,
+}
+
+impl ReplayCommand {
+    fn parse(line: &str) -> Result<Self, String> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            [] | ["p"] => Ok(ReplayCommand::TogglePause),
+            ["q"] => Ok(ReplayCommand::Quit),
+            ["speed", speed] => parse_speed(speed).map(ReplayCommand::Speed),
+            ["seek", at] => parse_duration(at).map(ReplayCommand::Seek),
+            [step] if step.starts_with('+') => {
+                parse_duration(&step[1..]).map(ReplayCommand::Forward)
+            }
+            [step] if step.starts_with('-') => {
+                parse_duration(&step[1..]).map(ReplayCommand::Backward)
+            }
+            _ => Err(format!("Unknown command {line}")),
+        }
+    }
+}
+
+/// Plays a recording through `print_state`, commands are read line by line from stdin
+#[derive(Debug)]
+pub struct Replay {
+    records: Vec<TraceRecord>,
+    visualizer: Visualizer,
+    /// Virtual instant of the start of the recording
+    started_at: Instant,
+    position: Duration,
+    next_record: usize,
+    speed: f64,
+    paused: bool,
+    status: String,
+}
+
+impl Replay {
+    pub fn new(mut records: Vec<TraceRecord>, speed: f64) -> Result<Self, String> {
+        if !speed.is_finite() || speed < 0.0 {
+            return Err(format!("Invalid speed {speed}"));
+        }
+        let init = records
+            .iter()
+            .position(|record| matches!(record.message, VisualizerMessages::Init { .. }))
+            .ok_or("Recording has no init message")?;
+        records.drain(..init);
+        let started_at = Instant::now();
+        let visualizer = Self::initial_visualizer(&records, started_at);
+        Ok(Self {
+            records,
+            visualizer,
+            started_at,
+            position: Duration::ZERO,
+            // The init record is consumed by the visualizer constructor
+            next_record: 1,
+            speed,
+            paused: false,
+            status: String::new(),
+        })
+    }
+
+    fn initial_visualizer(records: &[TraceRecord], started_at: Instant) -> Visualizer {
+        let VisualizerMessages::Init { thinkers, forks } = &records[0].message else {
+            unreachable!("Replay starts at the init message")
+        };
+        // Nothing is ever sent during a replay, the transceiver is only there to satisfy the visualizer
+        let transceiver = Transceiver::with_transport(Box::new(MemoryNetwork::new().bind()));
+        let mut visualizer = Visualizer::new(transceiver, thinkers.clone(), forks.clone());
+        visualizer.set_now(started_at + records[0].elapsed);
+        visualizer
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.records
+            .last()
+            .map(|record| record.elapsed - self.records[0].elapsed)
+            .unwrap_or_default()
+    }
+
+    /// Moves to `position`, going back means rebuilding the state from the start
+    pub fn seek(&mut self, position: Duration) {
+        let position = position.min(self.duration());
+        if position < self.position {
+            self.visualizer = Self::initial_visualizer(&self.records, self.started_at);
+            self.next_record = 1;
+        }
+        self.position = position;
+        let offset = self.records[0].elapsed;
+        while let Some(record) = self.records.get(self.next_record)
+            && record.elapsed - offset <= position
+        {
+            self.visualizer
+                .set_now(self.started_at + record.elapsed - offset);
+            self.visualizer
+                .handle_message(record.message.clone(), record.sender);
+            self.next_record += 1;
+        }
+        self.visualizer.set_now(self.started_at + position);
+    }
+
+    fn handle_command(&mut self, command: ReplayCommand) -> bool {
+        match command {
+            ReplayCommand::TogglePause => self.paused = !self.paused,
+            ReplayCommand::Speed(speed) => self.speed = speed,
+            ReplayCommand::Seek(position) => self.seek(position),
+            ReplayCommand::Forward(step) => self.seek(self.position + step),
+            ReplayCommand::Backward(step) => self.seek(self.position.saturating_sub(step)),
+            ReplayCommand::Quit => return false,
+        }
+        true
+    }
+
+    fn print_state(&self) {
+        self.visualizer.print_state();
+        println!();
+        println!(
+            "replay {:.1}s / {:.1}s, speed {}x{}",
+            self.position.as_secs_f64(),
+            self.duration().as_secs_f64(),
+            self.speed,
+            match self.paused {
+                true => " [paused]",
+                false => "",
+            }
+        );
+        println!("commands: <enter>/p pause, speed <x>, seek <t>, +<t>, -<t>, q quit");
+        if !self.status.is_empty() {
+            println!("{}", self.status);
+        }
+    }
+
+    pub fn run(mut self) {
+        let commands = spawn_stdin_reader();
+        let mut last_tick = Instant::now();
+        loop {
+            while let Ok(line) = commands.try_recv() {
+                match ReplayCommand::parse(&line) {
+                    Ok(command) => {
+                        self.status.clear();
+                        if !self.handle_command(command) {
+                            return;
+                        }
+                    }
+                    Err(e) => self.status = e,
+                }
+            }
+            let elapsed = last_tick.elapsed();
+            last_tick = Instant::now();
+            if !self.paused {
+                // A huge speed jumps to the end instead of overflowing
+                let step = Duration::try_from_secs_f64(elapsed.as_secs_f64() * self.speed)
+                    .unwrap_or(Duration::MAX);
+                self.seek(self.position.saturating_add(step));
+                if self.position >= self.duration() {
+                    self.paused = true;
+                    self.status = "End of recording".to_string();
+                }
+            }
+            self.print_state();
+            std::thread::sleep(TICK_INTERVAL);
+        }
+    }
+}
+
+fn spawn_stdin_reader() -> Receiver<String> {
+    let (sender, receiver) = channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
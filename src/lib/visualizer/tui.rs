@@ -0,0 +1,426 @@
+use std::f64::consts::TAU;
+use std::time::Instant;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::canvas::{Canvas, Line as CanvasLine};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use super::{ForkState, ThinkerState, Visualizer};
+use crate::lib::messages::ControlMessage;
+use crate::lib::messages::visualizer_messages::{
+    VisualizerForkState, VisualizerThinkerAvailableTokenState, VisualizerThinkerState,
+};
+use crate::{KEEP_ALIVE_TIMEOUT, KEEP_MESSAGE_PERCENTAGE, MIN_CRASH_DURATION, TICK_INTERVAL};
+
+/// Entries of the node list, in ring order like `print_state`
+#[derive(Debug, Clone, Copy)]
+enum Node {
+    Fork(usize),
+    Thinker(usize),
+}
+
+#[derive(Debug)]
+struct App {
+    list_state: ListState,
+    status: String,
+}
+
+fn is_alive(visualizer: &Visualizer, last_seen: Instant) -> bool {
+    visualizer.since(last_seen) <= KEEP_ALIVE_TIMEOUT
+}
+
+fn thinker_color(visualizer: &Visualizer, thinker_state: &ThinkerState) -> Color {
+    if !is_alive(visualizer, thinker_state.last_seen) {
+        return Color::DarkGray;
+    }
+    match thinker_state.visualizer_thinker_state {
+        VisualizerThinkerState::Thinking => Color::Blue,
+        VisualizerThinkerState::Hungry => Color::Yellow,
+        VisualizerThinkerState::WaitingForForks { .. } => Color::Magenta,
+        VisualizerThinkerState::Eating { .. } => Color::Green,
+    }
+}
+
+fn fork_color(visualizer: &Visualizer, fork_state: &ForkState) -> Color {
+    if !is_alive(visualizer, fork_state.last_seen) {
+        return Color::DarkGray;
+    }
+    match fork_state.visualizer_fork_state {
+        VisualizerForkState::Unused => Color::Gray,
+        VisualizerForkState::Used(_) => Color::Red,
+    }
+}
+
+fn last_seen_str(visualizer: &Visualizer, last_seen: Instant) -> String {
+    let since = visualizer.since(last_seen);
+    match is_alive(visualizer, last_seen) {
+        true => format!("{}ms ago", since.as_millis()),
+        false => format!("{:.1}s ago (dead)", since.as_secs_f64()),
+    }
+}
+
+impl App {
+    fn nodes(visualizer: &Visualizer) -> Vec<Node> {
+        (0..visualizer.thinkers.len().max(visualizer.forks.len()))
+            .flat_map(|i| {
+                (i < visualizer.forks.len())
+                    .then_some(Node::Fork(i))
+                    .into_iter()
+                    .chain((i < visualizer.thinkers.len()).then_some(Node::Thinker(i)))
+            })
+            .collect()
+    }
+
+    fn selected(&self, visualizer: &Visualizer) -> Option<Node> {
+        Self::nodes(visualizer)
+            .get(self.list_state.selected()?)
+            .copied()
+    }
+
+    fn send_control(&mut self, visualizer: &Visualizer, message: ControlMessage) {
+        let Some(node) = self.selected(visualizer) else {
+            return;
+        };
+        self.status = format!("Sent {message:?} to {node:?}");
+        match node {
+            Node::Fork(index) => visualizer.send_fork_control(index, message),
+            Node::Thinker(index) => visualizer.send_thinker_control(index, message),
+        }
+    }
+
+    /// Returns false once the user wants to quit
+    fn handle_key(&mut self, visualizer: &Visualizer, key: KeyCode) -> bool {
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Down | KeyCode::Char('j') => self.list_state.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => self.list_state.select_previous(),
+            KeyCode::Char('c') => self.send_control(
+                visualizer,
+                ControlMessage::Crash {
+                    duration: MIN_CRASH_DURATION,
+                },
+            ),
+            KeyCode::Char('x') => self.send_control(visualizer, ControlMessage::CrashPermanently),
+            KeyCode::Char('p') => self.send_control(visualizer, ControlMessage::Pause),
+            KeyCode::Char('r') => self.send_control(visualizer, ControlMessage::Resume),
+            KeyCode::Char('d') => {
+                self.send_control(visualizer, ControlMessage::DropMessages { percentage: 0.5 })
+            }
+            KeyCode::Char('n') => self.send_control(
+                visualizer,
+                ControlMessage::DropMessages {
+                    percentage: 1.0 - KEEP_MESSAGE_PERCENTAGE,
+                },
+            ),
+            _ => (),
+        }
+        true
+    }
+
+    fn draw(&mut self, frame: &mut Frame, visualizer: &Visualizer) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [left, right] =
+            Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)])
+                .areas(main);
+        let [ring, events] =
+            Layout::vertical([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(left);
+        let [list, detail] =
+            Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(right);
+
+        let selected = self.selected(visualizer);
+        Self::draw_ring(frame, ring, visualizer, selected);
+        Self::draw_events(frame, events, visualizer);
+        self.draw_list(frame, list, visualizer);
+        Self::draw_detail(frame, detail, visualizer, selected);
+        frame.render_widget(
+            Paragraph::new(format!(
+                "↑/↓ select  c crash  x crash permanently  p pause  r resume  d drop 50%  n normal drop  q quit   {}",
+                self.status
+            ))
+            .style(Style::default().add_modifier(Modifier::REVERSED)),
+            status,
+        );
+    }
+
+    fn draw_ring(frame: &mut Frame, area: Rect, visualizer: &Visualizer, selected: Option<Node>) {
+        let count = visualizer.thinkers.len().max(1) as f64;
+        let position = |angle: f64| (angle.sin(), angle.cos());
+        let thinker_position = |index: usize| position(TAU * index as f64 / count);
+        let fork_position = |index: usize| position(TAU * (index as f64 - 0.5) / count);
+        let highlight = |node: Node, style: Style| match (node, selected) {
+            (Node::Fork(a), Some(Node::Fork(b))) | (Node::Thinker(a), Some(Node::Thinker(b)))
+                if a == b =>
+            {
+                style.add_modifier(Modifier::REVERSED)
+            }
+            _ => style,
+        };
+
+        let canvas = Canvas::default()
+            .block(Block::bordered().title("Ring"))
+            .x_bounds([-1.3, 1.3])
+            .y_bounds([-1.3, 1.3])
+            .paint(|ctx| {
+                // Holder edges first so the labels are drawn on top
+                for (index, fork_state) in visualizer.forks.iter().enumerate() {
+                    if let VisualizerForkState::Used(holder) = &fork_state.visualizer_fork_state
+                        && let Some(thinker_index) = visualizer.thinker_index(holder)
+                        && is_alive(visualizer, fork_state.last_seen)
+                    {
+                        let (x1, y1) = fork_position(index);
+                        let (x2, y2) = thinker_position(thinker_index);
+                        ctx.draw(&CanvasLine {
+                            x1,
+                            y1,
+                            x2,
+                            y2,
+                            color: Color::Red,
+                        });
+                    }
+                }
+                ctx.layer();
+                for (index, fork_state) in visualizer.forks.iter().enumerate() {
+                    let (x, y) = fork_position(index);
+                    ctx.print(
+                        x,
+                        y,
+                        Span::styled(
+                            format!("f{index}"),
+                            highlight(
+                                Node::Fork(index),
+                                Style::default().fg(fork_color(visualizer, fork_state)),
+                            ),
+                        ),
+                    );
+                }
+                for (index, thinker_state) in visualizer.thinkers.iter().enumerate() {
+                    let (x, y) = thinker_position(index);
+                    ctx.print(
+                        x,
+                        y,
+                        Span::styled(
+                            format!(
+                                "T{index} {}",
+                                thinker_state.visualizer_thinker_state.symbol()
+                            ),
+                            highlight(
+                                Node::Thinker(index),
+                                Style::default()
+                                    .fg(thinker_color(visualizer, thinker_state))
+                                    .add_modifier(Modifier::BOLD),
+                            ),
+                        ),
+                    );
+                }
+            });
+        frame.render_widget(canvas, area);
+    }
+
+    fn draw_list(&mut self, frame: &mut Frame, area: Rect, visualizer: &Visualizer) {
+        let items = Self::nodes(visualizer)
+            .into_iter()
+            .map(|node| match node {
+                Node::Fork(index) => {
+                    let fork_state = &visualizer.forks[index];
+                    ListItem::new(format!(
+                        "  🍴 f{index:<3} [{}] {:<8} {}",
+                        fork_state.visualizer_fork_state.symbol(),
+                        fork_state.visualizer_fork_state.name(),
+                        Visualizer::short_id(&fork_state.fork.id)
+                    ))
+                    .style(Style::default().fg(fork_color(visualizer, fork_state)))
+                }
+                Node::Thinker(index) => {
+                    let thinker_state = &visualizer.thinkers[index];
+                    ListItem::new(format!(
+                        "🧐 T{index:<3} [{}] {:<15} {}",
+                        thinker_state.visualizer_thinker_state.symbol(),
+                        thinker_state.visualizer_thinker_state.name(),
+                        Visualizer::short_id(&thinker_state.thinker.id)
+                    ))
+                    .style(Style::default().fg(thinker_color(visualizer, thinker_state)))
+                }
+            })
+            .chain(visualizer.unregistered().map(|node| {
+                ListItem::new(format!(
+                    "?  {:<7} [{}] {} {}",
+                    node.kind,
+                    node.state,
+                    &node.id.to_string()[..4],
+                    node.address
+                ))
+                .style(Style::default().fg(Color::DarkGray))
+            }))
+            .collect::<Vec<_>>();
+        let title = match visualizer.unregistered().count() {
+            0 => "Nodes".to_string(),
+            count => format!("Nodes ({count} unregistered at the end)"),
+        };
+        let list = List::new(items)
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, area, &mut self.list_state);
+    }
+
+    fn draw_detail(frame: &mut Frame, area: Rect, visualizer: &Visualizer, selected: Option<Node>) {
+        let lines = match selected {
+            None => vec![Line::from("Nothing selected")],
+            Some(Node::Fork(index)) => {
+                let fork_state = &visualizer.forks[index];
+                let thinker_name = |id| match visualizer.thinker_index(id) {
+                    Some(thinker_index) => format!("T{thinker_index}"),
+                    None => format!("{id}"),
+                };
+                vec![
+                    Line::from(format!("Fork f{index}")),
+                    Line::from(format!("id:        {}", fork_state.fork.id)),
+                    Line::from(format!("address:   {}", fork_state.fork.address)),
+                    Line::from(format!(
+                        "backups:   {}",
+                        fork_state
+                            .fork
+                            .backups
+                            .iter()
+                            .map(|address| address.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )),
+                    Line::from(format!(
+                        "state:     {}",
+                        match &fork_state.visualizer_fork_state {
+                            VisualizerForkState::Unused => "Unused".to_string(),
+                            VisualizerForkState::Used(holder) =>
+                                format!("Used by {}", thinker_name(holder)),
+                        }
+                    )),
+                    Line::from(format!(
+                        "queue:     [{}]",
+                        fork_state
+                            .queue
+                            .iter()
+                            .map(thinker_name)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )),
+                    Line::from(format!(
+                        "last seen: {}",
+                        last_seen_str(visualizer, fork_state.last_seen)
+                    )),
+                ]
+            }
+            Some(Node::Thinker(index)) => {
+                let thinker_state = &visualizer.thinkers[index];
+                let mut lines = vec![
+                    Line::from(format!("Thinker T{index}")),
+                    Line::from(format!("id:        {}", thinker_state.thinker.id)),
+                    Line::from(format!("address:   {}", thinker_state.thinker.address)),
+                    Line::from(format!(
+                        "state:     {}",
+                        thinker_state.visualizer_thinker_state.name()
+                    )),
+                ];
+                if let VisualizerThinkerState::WaitingForForks { token }
+                | VisualizerThinkerState::Eating { token } =
+                    &thinker_state.visualizer_thinker_state
+                {
+                    lines.push(Line::from(format!(
+                        "token:     {} v{}",
+                        Visualizer::short_id(&token.id),
+                        token.version
+                    )));
+                }
+                lines.push(Line::from(format!(
+                    "last seen: {}",
+                    last_seen_str(visualizer, thinker_state.last_seen)
+                )));
+                lines.push(Line::from("tokens:"));
+                lines.extend(
+                    thinker_state
+                        .visualizer_available_token_state
+                        .iter()
+                        .enumerate()
+                        .map(|(token_index, token_state)| {
+                            Line::from(match token_state {
+                                VisualizerThinkerAvailableTokenState::Passive { not_seen_for } => {
+                                    format!(
+                                        "  {token_index}: passive, not seen for {}ms",
+                                        not_seen_for.as_millis()
+                                    )
+                                }
+                                VisualizerThinkerAvailableTokenState::Propose {
+                                    token_version,
+                                    propose_version,
+                                } => format!(
+                                    "  {token_index}: proposing p{propose_version} -> v{token_version}"
+                                ),
+                            })
+                        }),
+                );
+                lines
+            }
+        };
+        frame.render_widget(
+            Paragraph::new(lines)
+                .block(Block::bordered().title("Details"))
+                .wrap(Wrap { trim: false }),
+            area,
+        );
+    }
+
+    fn draw_events(frame: &mut Frame, area: Rect, visualizer: &Visualizer) {
+        let height = area.height.saturating_sub(2) as usize;
+        let lines = visualizer
+            .events()
+            .rev()
+            .take(height)
+            .map(|event| {
+                Line::from(format!(
+                    "{:>7.1}s ago  {}",
+                    visualizer.since(event.at).as_secs_f64(),
+                    event.description
+                ))
+            })
+            .collect::<Vec<_>>();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Events")),
+            area,
+        );
+    }
+}
+
+/// Runs the full screen visualizer until `q` is pressed
+pub fn run(visualizer: &mut Visualizer, buffer: &mut [u8]) -> std::io::Result<()> {
+    // Log lines on stderr would tear the alternate screen
+    log::set_max_level(log::LevelFilter::Off);
+    let mut terminal = ratatui::init();
+    let result = run_loop(&mut terminal, visualizer, buffer);
+    ratatui::restore();
+    result
+}
+
+fn run_loop(
+    terminal: &mut DefaultTerminal,
+    visualizer: &mut Visualizer,
+    buffer: &mut [u8],
+) -> std::io::Result<()> {
+    let mut app = App {
+        list_state: ListState::default().with_selected(Some(0)),
+        status: String::new(),
+    };
+    loop {
+        visualizer.receive(buffer);
+        terminal.draw(|frame| app.draw(frame, visualizer))?;
+        if event::poll(TICK_INTERVAL)?
+            && let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+            && !app.handle_key(visualizer, key.code)
+        {
+            return Ok(());
+        }
+    }
+}
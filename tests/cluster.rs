@@ -0,0 +1,179 @@
+use std::collections::BTreeSet;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use philosopher_nom_nom_ring::lib::chaos::{ChaosTarget, NodeRef, Scenario, run_scenario};
+use philosopher_nom_nom_ring::lib::cluster::{Cluster, ClusterBuilder, ClusterTransport};
+use philosopher_nom_nom_ring::lib::events::{Event, EventRecord};
+use philosopher_nom_nom_ring::lib::faults::FaultConfig;
+use philosopher_nom_nom_ring::lib::fork::replica_node;
+use philosopher_nom_nom_ring::lib::messages::ControlMessage;
+use philosopher_nom_nom_ring::lib::messages::visualizer_messages::VisualizerThinkerState;
+use philosopher_nom_nom_ring::lib::thinker::MealTiming;
+
+fn short_meals() -> MealTiming {
+    MealTiming {
+        thinking: Duration::from_millis(100)..=Duration::from_millis(300),
+        eating: Duration::from_millis(100)..=Duration::from_millis(300),
+    }
+}
+
+/// Steps the cluster like `Cluster` does and checks after every step that no neighbours eat at once
+struct Checked {
+    cluster: Cluster,
+    started_at: Instant,
+    /// Wall time of the start, events are stamped with it
+    started_ms: u64,
+    events: Vec<EventRecord>,
+}
+
+impl Checked {
+    fn new(cluster: Cluster) -> Self {
+        Self {
+            cluster,
+            started_at: Instant::now(),
+            started_ms: now_ms(),
+            events: vec![],
+        }
+    }
+
+    fn run_for(&mut self, duration: Duration) {
+        let until = Instant::now() + duration;
+        while Instant::now() < until {
+            self.cluster.run_for(Duration::from_millis(1));
+            let states = self.cluster.thinker_states();
+            let eating = |index: usize| {
+                matches!(
+                    states[index % states.len()],
+                    Some(VisualizerThinkerState::Eating { .. })
+                )
+            };
+            for index in 0..states.len() {
+                assert!(
+                    !(eating(index) && eating(index + 1)),
+                    "Thinkers {index} and {} eat at once",
+                    (index + 1) % states.len()
+                );
+            }
+        }
+    }
+
+    /// Thinkers by index that started eating since `since`, only known once the events were taken
+    fn eaters_since(&self, since: u64) -> BTreeSet<usize> {
+        let thinkers = &self.cluster.topology().thinkers;
+        self.events
+            .iter()
+            .filter(|record| record.wall_time_ms >= since)
+            .filter(|record| matches!(record.event, Event::StartedEating { .. }))
+            .filter_map(|record| {
+                thinkers
+                    .iter()
+                    .position(|thinker| thinker.id.value == record.node)
+            })
+            .collect()
+    }
+}
+
+impl ChaosTarget for Checked {
+    fn nodes(&self) -> Vec<NodeRef> {
+        self.cluster.nodes()
+    }
+
+    fn addresses(&self, node: NodeRef) -> Vec<SocketAddr> {
+        self.cluster.addresses(node)
+    }
+
+    fn send_control(&mut self, node: NodeRef, message: ControlMessage) {
+        self.cluster.send_control(node, message)
+    }
+
+    fn wait_until(&mut self, at: Duration) {
+        if let Some(remaining) = at.checked_sub(self.started_at.elapsed()) {
+            self.run_for(remaining);
+        }
+    }
+
+    fn take_events(&mut self) -> Vec<EventRecord> {
+        let events = self.cluster.take_events();
+        self.events.extend(events.iter().cloned());
+        events
+    }
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+#[test]
+fn tokens_circulate_without_neighbours_eating_at_once() {
+    // Without loss no token has to wait for its regeneration
+    let cluster = ClusterBuilder::new(5)
+        .tokens(2)
+        .timing(short_meals())
+        .fault_config(FaultConfig {
+            drop_percentage: 0.0,
+            ..FaultConfig::default()
+        })
+        .transport(ClusterTransport::Memory)
+        .seed(1)
+        .build();
+    let mut checked = Checked::new(cluster);
+    checked.run_for(Duration::from_secs(8));
+    checked.take_events();
+    assert_eq!(
+        checked.eaters_since(0),
+        (0..5).collect(),
+        "Every thinker should get a token"
+    );
+}
+
+#[test]
+fn fork_fails_over_during_a_chaos_scenario() {
+    let cluster = ClusterBuilder::new(4)
+        .tokens(2)
+        .fork_replicas(3)
+        .timing(short_meals())
+        .transport(ClusterTransport::Memory)
+        .seed(2)
+        .build();
+    let fork = cluster.topology().forks[0].id.clone();
+    let mut checked = Checked::new(cluster);
+    let scenario = Scenario::parse(
+        "t=1s crash f0.0 for 8s\n\
+         t=4s partition p {0,1} from {2,3}\n\
+         t=6s heal",
+    )
+    .unwrap();
+    run_scenario(
+        &scenario,
+        &mut checked,
+        Duration::from_secs(6),
+        &mut std::io::sink(),
+    );
+
+    let backups = [1, 2].map(|rank| replica_node(&fork, rank));
+    let failed_over_at = checked
+        .events
+        .iter()
+        .find(|record| {
+            backups.contains(&record.node)
+                && matches!(record.event, Event::BecamePrimary { epoch } if epoch > 0)
+        })
+        .map(|record| record.wall_time_ms)
+        .expect("A backup of fork 0 should take over");
+    // Thinker 0 and thinker 3 share fork 0
+    let eaters = checked.eaters_since(failed_over_at);
+    assert!(
+        eaters.contains(&0) && eaters.contains(&3),
+        "The neighbours of fork 0 should eat after the failover, only {eaters:?} did"
+    );
+    let healed_at = checked.started_ms + 6000;
+    assert_eq!(
+        checked.eaters_since(healed_at),
+        (0..4).collect(),
+        "Every thinker should eat again after the partition healed"
+    );
+}